@@ -0,0 +1,102 @@
+pub extern crate log;
+extern crate struct_diff;
+
+use log::kv::{Error, Key, Source, Value, VisitSource};
+use struct_diff::Difference;
+
+/// Passes differences to `log` as key/values, three per changed path:
+/// `diff.<path>.kind`, `diff.<path>.left` and `diff.<path>.right`.
+///
+/// ```
+/// # extern crate log;
+/// # extern crate log_struct_diff;
+/// # extern crate struct_diff;
+/// # use log_struct_diff::Differences;
+/// # use struct_diff::Diff;
+/// # fn main() {
+/// let diffs = Differences::new(&[1, 2].diff(&[1, 3]).unwrap_or_default());
+/// log::logger().log(&log::Record::builder()
+///     .args(format_args!("changed"))
+///     .level(log::Level::Info)
+///     .key_values(&diffs)
+///     .build());
+/// # }
+/// ```
+pub struct Differences {
+    pairs: Vec<(String, String)>,
+}
+
+impl Differences {
+    pub fn new(diffs: &[Difference]) -> Self {
+        let mut pairs = Vec::with_capacity(diffs.len() * 3);
+        for diff in diffs {
+            let prefix = if diff.field.is_empty() {
+                "diff".to_string()
+            } else {
+                format!("diff.{}", diff.field)
+            };
+            pairs.push((format!("{}.kind", prefix), diff.kind.name().to_string()));
+            pairs.push((format!("{}.left", prefix), format!("{:?}", diff.left)));
+            pairs.push((format!("{}.right", prefix), format!("{:?}", diff.right)));
+        }
+        Differences { pairs }
+    }
+}
+
+impl<'a, 'b> From<&'b [Difference<'a>]> for Differences {
+    fn from(diffs: &'b [Difference<'a>]) -> Self {
+        Differences::new(diffs)
+    }
+}
+
+impl Source for Differences {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), Error> {
+        for &(ref key, ref value) in self.pairs.iter() {
+            visitor.visit_pair(Key::from_str(key), Value::from(value.as_str()))?;
+        }
+        Ok(())
+    }
+
+    fn count(&self) -> usize {
+        self.pairs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use struct_diff::Diff;
+
+    /// Collects visited key/values as strings
+    struct Capture(Vec<(String, String)>);
+
+    impl<'kvs> VisitSource<'kvs> for Capture {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+            self.0.push((key.to_string(), value.to_string()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn differences_key_values() {
+        let (a, b) = ([1, 2, 3], [1, 5, 3]);
+        let diffs = Differences::new(&a.diff(&b).unwrap());
+        let mut capture = Capture(Vec::new());
+        diffs.visit(&mut capture).unwrap();
+        assert_eq!(capture.0, vec![
+            ("diff.[1].kind".to_string(), "modified".to_string()),
+            ("diff.[1].left".to_string(), "2".to_string()),
+            ("diff.[1].right".to_string(), "5".to_string()),
+        ]);
+        assert_eq!(diffs.count(), 3);
+        assert_eq!(diffs.get(Key::from_str("diff.[1].right")).unwrap().to_string(), "5");
+    }
+
+    #[test]
+    fn differences_whole_value() {
+        let diffs = Differences::new(&Some(1).diff(&None).unwrap());
+        let mut capture = Capture(Vec::new());
+        diffs.visit(&mut capture).unwrap();
+        assert_eq!(capture.0[0], ("diff.kind".to_string(), "removed".to_string()));
+    }
+}
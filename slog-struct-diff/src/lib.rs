@@ -1,9 +1,21 @@
+extern crate erased_serde;
+extern crate serde;
 extern crate slog;
 extern crate struct_diff;
 
-use struct_diff::{Diff, Difference};
-use slog::{KV, Result, Record, Serializer};
+use serde::ser::{Serialize, SerializeSeq, SerializeStruct, Serializer as SerdeSerializer};
+use struct_diff::Difference;
+use slog::{Key, KV, Result, Record, SerdeValue, Serializer, Value};
 
+/// Logs all differences under a single nested `diff` key, as a sequence of
+/// `{"path": .., "kind": .., "left": .., "right": ..}` records in the order
+/// they were found, so a path reported twice is kept twice.
+///
+/// Values are rendered with `Debug`: a `Difference` only holds them as `&Debug`,
+/// their types and any `Serialize` impl being erased by then.
+///
+/// Loggers without nested values support get the differences
+/// as one `path: left -> right; ..` string instead.
 pub struct Differences<'a>(pub Vec<SlogDifference<'a>>);
 pub struct SlogDifference<'a>(pub Difference<'a>);
 
@@ -15,6 +27,7 @@ impl<'a> KV for SlogDifference<'a> {
         serializer: &mut Serializer,
     ) -> Result {
         serializer.emit_str("field", self.0.field.as_str())?;
+        serializer.emit_str("kind", self.0.kind.name())?;
         serializer.emit_str("left", format!("{:?}", self.0.left).as_str())?;
         serializer.emit_str("right", format!("{:?}", self.0.right).as_str())?;
         Ok(())
@@ -27,11 +40,168 @@ impl<'a> KV for Differences<'a> {
         _record: &Record,
         serializer: &mut Serializer,
     ) -> Result {
-        for (index, item) in self.0.iter().enumerate() {
-            serializer.emit_usize("field_number", index)?;
-            item.serialize(_record, serializer)?;
+        let rendered = RenderedDifferences(self.0.iter().map(|item| RenderedDifference::new(&item.0)).collect());
+        serializer.emit_serde("diff", &rendered)
+    }
+}
+
+/// Owned rendering of differences, as loggers may keep values around
+#[derive(Clone)]
+struct RenderedDifferences(Vec<RenderedDifference>);
+
+#[derive(Clone)]
+struct RenderedDifference {
+    field: String,
+    kind: &'static str,
+    left: String,
+    right: String,
+}
+
+impl RenderedDifference {
+    fn new(diff: &Difference) -> Self {
+        RenderedDifference {
+            field: diff.field.clone(),
+            kind: diff.kind.name(),
+            left: format!("{:?}", diff.left),
+            right: format!("{:?}", diff.right),
         }
-        Ok(())
     }
 }
 
+impl Serialize for RenderedDifferences {
+    fn serialize<S: SerdeSerializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for diff in self.0.iter() {
+            seq.serialize_element(diff)?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for RenderedDifference {
+    fn serialize<S: SerdeSerializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        let mut record = serializer.serialize_struct("Difference", 4)?;
+        record.serialize_field("path", &self.field)?;
+        record.serialize_field("kind", self.kind)?;
+        record.serialize_field("left", &self.left)?;
+        record.serialize_field("right", &self.right)?;
+        record.end()
+    }
+}
+
+impl Value for RenderedDifferences {
+    fn serialize(
+        &self,
+        _record: &Record,
+        key: Key,
+        serializer: &mut Serializer,
+    ) -> Result {
+        serializer.emit_serde(key, self)
+    }
+}
+
+impl SerdeValue for RenderedDifferences {
+    fn serialize_fallback(&self, key: Key, serializer: &mut Serializer) -> Result {
+        let rendered: Vec<_> = self.0.iter()
+            .map(|diff| format!("{}: {} -> {}", diff.field, diff.left, diff.right))
+            .collect();
+        serializer.emit_str(key, &rendered.join("; "))
+    }
+
+    fn as_serde(&self) -> &erased_serde::Serialize {
+        self
+    }
+
+    fn to_sendable(&self) -> Box<SerdeValue + Send + 'static> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::*;
+    use slog::{Drain, Logger, OwnedKVList};
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use struct_diff::Diff;
+
+    /// Collects key/values of logged records as strings,
+    /// nested values are rendered as JSON if `nested` is set
+    struct Capture {
+        nested: bool,
+        values: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Serializer for Capture {
+        fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+            self.values.lock().unwrap().push((key.to_string(), val.to_string()));
+            Ok(())
+        }
+
+        fn emit_serde(&mut self, key: Key, value: &SerdeValue) -> Result {
+            if !self.nested {
+                return value.serialize_fallback(key, self);
+            }
+            let json = serde_json::to_string(value.as_serde()).unwrap();
+            self.values.lock().unwrap().push((key.to_string(), json));
+            Ok(())
+        }
+    }
+
+    impl Drain for Capture {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(&self, record: &Record, _values: &OwnedKVList) -> ::std::result::Result<(), slog::Never> {
+            let mut serializer = Capture { nested: self.nested, values: self.values.clone() };
+            record.kv().serialize(record, &mut serializer).unwrap();
+            Ok(())
+        }
+    }
+
+    fn log_diffs(nested: bool) -> Vec<(String, String)> {
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let logger = Logger::root(Capture { nested, values: values.clone() }, slog::o!());
+        let (a, b) = ([1, 2, 3], [1, 5, 6]);
+        let diffs = Differences(a.diff(&b).unwrap().into_iter().map(SlogDifference).collect());
+        slog::info!(logger, "changed"; diffs);
+        let values = values.lock().unwrap();
+        values.clone()
+    }
+
+    #[test]
+    fn differences_nested() {
+        assert_eq!(log_diffs(true), vec![(
+            "diff".to_string(),
+            concat!(
+                r#"[{"path":"[1]","kind":"modified","left":"2","right":"5"},"#,
+                r#"{"path":"[2]","kind":"modified","left":"3","right":"6"}]"#,
+            ).to_string(),
+        )]);
+    }
+
+    #[test]
+    fn differences_same_path() {
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let logger = Logger::root(Capture { nested: true, values: values.clone() }, slog::o!());
+        let change = |left: &'static u32, right: &'static u32| SlogDifference(Difference {
+            field: "a".to_string(),
+            kind: struct_diff::ChangeKind::Modified,
+            left,
+            right,
+        });
+        slog::info!(logger, "changed"; Differences(vec![change(&1, &2), change(&2, &3)]));
+        let values = values.lock().unwrap();
+        assert_eq!(values[0].1, concat!(
+            r#"[{"path":"a","kind":"modified","left":"1","right":"2"},"#,
+            r#"{"path":"a","kind":"modified","left":"2","right":"3"}]"#,
+        ));
+    }
+
+    #[test]
+    fn differences_fallback() {
+        assert_eq!(log_diffs(false), vec![("diff".to_string(), "[1]: 2 -> 5; [2]: 3 -> 6".to_string())]);
+    }
+}
@@ -7,7 +7,49 @@ extern crate struct_diff;
 
 use proc_macro::TokenStream;
 
-#[proc_macro_derive(Diff)]
+/// Derives `struct_diff::Diff`.
+///
+/// Structs with named fields accept `#[diff(delta)]`, which also generates
+/// a `FooDelta<'a>` struct holding an `Option` of the typed delta of every
+/// field, and implements `struct_diff::Delta` for `Foo` to produce it.
+/// Field types need to implement `Delta` too: nested structs with
+/// `#[diff(delta)]` give their own `BarDelta`, other values a `struct_diff::Change`.
+/// Enums always implement `Delta` and change as a whole.
+///
+/// `#[diff(patch)]` similarly generates a `FooPatch` struct holding an `Option`
+/// of the patch of every field, and implements `struct_diff::Patch` for `Foo`,
+/// e.g. for `struct_diff::History::at`. Field types need to implement `Patch`.
+/// The `Debug` of `FooPatch` shows `redact` fields as `<redacted>`, and values
+/// replaced as a whole like `struct_diff::Diff::shown`, see `struct_diff::Patch::shown_patch`.
+/// Enums accept it too and are replaced as a whole, which needs them to be `Clone`.
+///
+/// They also accept `#[diff(fields)]`, which generates a `FooField` enum
+/// with a variant per field (`created_at` becomes `CreatedAt`) and
+/// `Foo::changed_fields(&self, other: &Foo) -> struct_diff::FieldSet<FooField>`.
+/// It stops at the first difference of every field and only allocates
+/// for `key` fields, see `struct_diff::has_diff_keyed`.
+///
+/// Fields accept `#[diff(...)]` attributes:
+///
+/// * `redact` - changes are still detected, with their kind, but both sides
+///   are reported as `<redacted>`. Values holding the field that are reported as a whole,
+///   e.g. on a variant change, with `DiffOptions::max_depth` or as an added
+///   element, are shown like `Debug` with the field as `<redacted>`,
+///   see `struct_diff::Diff::fmt_shown`.
+/// * `flatten` - the field's own differences are reported without its name
+///   in the path, matching `#[serde(flatten)]`: `created_by`, not `meta.created_by`.
+/// * `key = "id"` - on sequence fields, elements are matched by their `id`
+///   field instead of by position, see `struct_diff::diff_keyed`.
+///   Paths look like `items.[id=42].qty`, and `items.[id=42#2].qty` for the second
+///   element sharing a key.
+/// * `unordered` - on sequence fields, elements are compared as a multiset,
+///   only those missing on one side are reported, see `struct_diff::diff_unordered`.
+///   Paths look like `roles.[-2]` for removed and `roles.[+0]` for added elements.
+/// * `column = "name"` - with `#[diff(fields)]`, the database column of the field
+///   as returned by `FieldEnum::column`, e.g. for `struct_diff::update_sql`.
+/// * `entity_id` - on one field of a struct, implements `struct_diff::Entity`
+///   with the field's `Display` as the id, e.g. for `struct_diff::AuditRecord`.
+#[proc_macro_derive(Diff, attributes(diff))]
 pub fn generate_diff_impl(input: TokenStream) -> TokenStream {
     let s = input.to_string();
     // Parse the string representation
@@ -22,9 +64,201 @@ pub fn generate_diff_impl(input: TokenStream) -> TokenStream {
 
 fn impl_diff(ast: &syn::DeriveInput) -> quote::Tokens {
     let name = &ast.ident;
-    match ast.body {
+    let mut gen = match ast.body {
         syn::Body::Struct(ref vdata) => impl_diff_struct(name, &vdata),
         syn::Body::Enum(ref variants) => impl_diff_enum(name, &variants),
+    };
+    if let Some(entity) = impl_entity(name, &ast.body) {
+        gen.append(entity);
+    }
+    let attrs = TypeAttrs::parse(&ast.attrs);
+    if attrs.patch {
+        let patch = match ast.body {
+            syn::Body::Struct(syn::VariantData::Struct(ref fields)) if !fields.is_empty() => {
+                impl_patch(name, &ast.vis, fields)
+            },
+            syn::Body::Enum(_) => impl_patch_enum(name),
+            _ => panic!("#[diff(patch)] is only supported on enums and structs with named fields"),
+        };
+        gen.append(patch);
+    }
+    if attrs.delta || attrs.fields {
+        let fields = match ast.body {
+            syn::Body::Struct(syn::VariantData::Struct(ref fields)) if !fields.is_empty() => fields,
+            _ => panic!("#[diff(delta)] and #[diff(fields)] are only supported on structs with named fields"),
+        };
+        if attrs.delta {
+            gen.append(impl_delta(name, &ast.vis, fields));
+        }
+        if attrs.fields {
+            gen.append(impl_changed_fields(name, &ast.vis, fields));
+        }
+    }
+    gen
+}
+
+/// Implements Entity for structs with a field marked `#[diff(entity_id)]`
+fn impl_entity(name: &syn::Ident, body: &syn::Body) -> Option<quote::Tokens> {
+    let fields = match *body {
+        syn::Body::Struct(ref vdata) => vdata.fields(),
+        syn::Body::Enum(ref variants) => {
+            if variants.iter().flat_map(|variant| variant.data.fields()).any(|field| FieldAttrs::parse(field).entity_id) {
+                panic!("#[diff(entity_id)] is only supported on struct fields");
+            }
+            return None;
+        },
+    };
+    let mut ids = fields.iter().enumerate().filter(|&(_, field)| FieldAttrs::parse(field).entity_id);
+    let (i, field) = match ids.next() {
+        Some(id) => id,
+        None => return None,
+    };
+    if ids.next().is_some() {
+        panic!("#[diff(entity_id)] can only be set on one field of {}", name);
+    }
+    let field_name = field.ident.clone().unwrap_or(syn::Ident::from(i));
+    let name_s = name.to_string();
+    Some(quote! {
+        impl ::struct_diff::Entity for #name {
+            fn entity_name(&self) -> &'static str {
+                #name_s
+            }
+
+            fn entity_id(&self) -> String {
+                self.#field_name.to_string()
+            }
+        }
+    })
+}
+
+/// Options set on the derived type with `#[diff(...)]`
+#[derive(Default)]
+struct TypeAttrs {
+    delta: bool,
+    fields: bool,
+    patch: bool,
+}
+
+impl TypeAttrs {
+    fn parse(attrs: &[syn::Attribute]) -> Self {
+        let mut type_attrs = TypeAttrs::default();
+        for attr in attrs.iter() {
+            let items = match attr.value {
+                syn::MetaItem::List(ref name, ref items) if name == "diff" => items,
+                _ => continue,
+            };
+            for item in items {
+                match *item {
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "delta" => {
+                        type_attrs.delta = true;
+                    },
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "fields" => {
+                        type_attrs.fields = true;
+                    },
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "patch" => {
+                        type_attrs.patch = true;
+                    },
+                    _ => panic!("Unknown diff attribute {:?}", item),
+                }
+            }
+        }
+        type_attrs
+    }
+}
+
+/// Options set on a field with `#[diff(...)]`
+#[derive(Default)]
+struct FieldAttrs {
+    redact: bool,
+    flatten: bool,
+    /// Field of sequence elements to match them by
+    key: Option<String>,
+    unordered: bool,
+    /// Database column, defaults to the field name
+    column: Option<String>,
+    entity_id: bool,
+}
+
+impl FieldAttrs {
+    fn parse(field: &syn::Field) -> Self {
+        let mut attrs = FieldAttrs::default();
+        for attr in field.attrs.iter() {
+            let items = match attr.value {
+                syn::MetaItem::List(ref name, ref items) if name == "diff" => items,
+                _ => continue,
+            };
+            for item in items {
+                match *item {
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "redact" => {
+                        attrs.redact = true;
+                    },
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "flatten" => {
+                        attrs.flatten = true;
+                    },
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "unordered" => {
+                        attrs.unordered = true;
+                    },
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "entity_id" => {
+                        attrs.entity_id = true;
+                    },
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(ref name, syn::Lit::Str(ref key, _))) if name == "key" => {
+                        attrs.key = Some(key.clone());
+                    },
+                    syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(ref name, syn::Lit::Str(ref column, _))) if name == "column" => {
+                        attrs.column = Some(column.clone());
+                    },
+                    _ => panic!("Unknown diff attribute {:?}", item),
+                }
+            }
+        }
+        if attrs.redact && attrs.flatten {
+            panic!("diff attributes redact and flatten can't be combined on field {:?}", field.ident);
+        }
+        if attrs.key.is_some() && attrs.unordered {
+            panic!("diff attributes key and unordered can't be combined on field {:?}", field.ident);
+        }
+        attrs
+    }
+
+    /// `has_diff` of the field, `left` and `right` being references to it
+    fn has_diff(&self, left: &quote::Tokens, right: &quote::Tokens) -> quote::Tokens {
+        match self.key {
+            Some(ref key) => {
+                let key = syn::Ident::from(key.as_str());
+                quote!(::struct_diff::has_diff_keyed(&(#left)[..], &(#right)[..], |item| &item.#key))
+            },
+            None if self.unordered => quote!(::struct_diff::has_diff_unordered(&(#left)[..], &(#right)[..])),
+            None => quote!((#left).has_diff(#right)),
+        }
+    }
+
+    /// Kind of the change of the field reported as a single difference, if any,
+    /// `left` and `right` being references to it
+    fn change_kind(&self, left: &quote::Tokens, right: &quote::Tokens) -> quote::Tokens {
+        if self.key.is_some() || self.unordered {
+            let check = self.has_diff(left, right);
+            return quote! {
+                if #check {
+                    Some(::struct_diff::ChangeKind::Modified)
+                } else {
+                    None
+                }
+            };
+        }
+        quote!(::struct_diff::change_kind(#left, #right))
+    }
+
+    /// `diff_with` of the field, `left` and `right` being references to it
+    fn diff_with(&self, left: &quote::Tokens, right: &quote::Tokens) -> quote::Tokens {
+        match self.key {
+            Some(ref key) => {
+                let key_s = key.as_str();
+                let key = syn::Ident::from(key_s);
+                quote!(::struct_diff::diff_keyed(&(#left)[..], &(#right)[..], #key_s, |item| &item.#key, visitor))
+            },
+            None if self.unordered => quote!(::struct_diff::diff_unordered(&(#left)[..], &(#right)[..], visitor)),
+            None => quote!((#left).diff_with(#right, visitor)),
+        }
     }
 }
 
@@ -38,24 +272,13 @@ impl<'a> quote::ToTokens for StructGenerator<'a> {
         for field in self.fields.iter() {
             if let Some(ref field_name) = field.ident {
                 let field_name_s = field_name.to_string();
-                tokens.append(
-                    quote!{
-                        if let Some(inner_diffs) = self.#field_name.diff(&other.#field_name) {
-                            for diff in inner_diffs {
-                                let mut path = String::from(#field_name_s);
-                                if !diff.field.is_empty() {
-                                    path.push_str(&".");
-                                }
-                                path.push_str(&diff.field);
-                                diffs.push(::struct_diff::Difference {
-                                    field: path,
-                                    left: diff.left,
-                                    right: diff.right,
-                                })
-                            }
-                        }
-                    }
-                )
+                let visit = VisitGenerator {
+                    segment: quote!(::struct_diff::PathSegment::Field(#field_name_s)),
+                    left: quote!(&self.#field_name),
+                    right: quote!(&other.#field_name),
+                    attrs: FieldAttrs::parse(field),
+                };
+                tokens.append(quote!(#visit))
             }
         }
     }
@@ -69,6 +292,8 @@ struct FieldGenerator<'a> {
 
 impl<'a> quote::ToTokens for FieldGenerator<'a> {
     fn to_tokens(&self, tokens: &mut quote::Tokens) {
+        let name = self.name.to_string();
+        let mut inner = quote::Tokens::new();
         for (i, field) in self.fields.iter().enumerate() {
             let field_name = field.ident.clone().unwrap_or(syn::Ident::from(i));
             let field_name_s = field_name.to_string();
@@ -82,31 +307,117 @@ impl<'a> quote::ToTokens for FieldGenerator<'a> {
                     new_name.push_str(&field_name.to_string());
                     syn::Ident::from(new_name)
             };
-            let name = self.name.to_string();
-            tokens.append(
-                quote!{
-                    if let Some(inner_diffs) = #left.diff(&#right) {
-                        for diff in inner_diffs {
-                            let mut path = String::from(#name);
-                            path.push_str(".");
-                            path.push_str(&#field_name_s);
-                            if !diff.field.is_empty() {
-                                path.push_str(&".");
-                            }
-                            path.push_str(&diff.field);
-                            diffs.push(::struct_diff::Difference {
-                                field: path,
-                                left: diff.left,
-                                right: diff.right,
-                            })
-                        }
+            let visit = VisitGenerator {
+                segment: quote!(::struct_diff::PathSegment::Field(#field_name_s)),
+                left: quote!(#left),
+                right: quote!(#right),
+                attrs: FieldAttrs::parse(field),
+            };
+            inner.append(quote!(#visit))
+        }
+        tokens.append(quote!{
+            if visitor.enter_field(::struct_diff::PathSegment::Variant(#name)) {
+                if visitor.collapse() {
+                    if self.has_diff(other) {
+                        visitor.leaf(self.shown(), other.shown(), ::struct_diff::ChangeKind::Modified);
+                    }
+                } else {
+                    #inner
+                }
+                visitor.leave_field();
+            }
+        });
+    }
+}
+
+/// Generates descent into a single field, `left` and `right` being references to it
+struct VisitGenerator {
+    segment: quote::Tokens,
+    left: quote::Tokens,
+    right: quote::Tokens,
+    attrs: FieldAttrs,
+}
+
+impl quote::ToTokens for VisitGenerator {
+    fn to_tokens(&self, tokens: &mut quote::Tokens) {
+        let (segment, left, right) = (&self.segment, &self.left, &self.right);
+        if self.attrs.redact {
+            let kind = self.attrs.change_kind(left, right);
+            tokens.append(quote!{
+                if visitor.enter_field(#segment) {
+                    if let Some(kind) = #kind {
+                        visitor.leaf(&::struct_diff::Redacted, &::struct_diff::Redacted, kind);
+                    }
+                    visitor.leave_field();
+                }
+            });
+            return;
+        }
+        let check = self.attrs.has_diff(left, right);
+        let descend = self.attrs.diff_with(left, right);
+        if self.attrs.flatten {
+            tokens.append(quote!{
+                #descend;
+            });
+            return;
+        }
+        tokens.append(quote!{
+            if visitor.enter_field(#segment) {
+                if visitor.collapse() {
+                    if #check {
+                        visitor.leaf((#left).shown(), (#right).shown(), ::struct_diff::ChangeKind::Modified);
                     }
+                } else {
+                    #descend;
                 }
-            )
+                visitor.leave_field();
+            }
+        })
+    }
+}
+
+/// Generates the `Debug` builder calls of `fmt_shown` for fields,
+/// `values` being references to each of them
+struct ShownFieldsGenerator<'a> {
+    fields: &'a [syn::Field],
+    values: Vec<quote::Tokens>,
+}
+
+impl<'a> quote::ToTokens for ShownFieldsGenerator<'a> {
+    fn to_tokens(&self, tokens: &mut quote::Tokens) {
+        for (field, value) in self.fields.iter().zip(self.values.iter()) {
+            let shown = if FieldAttrs::parse(field).redact {
+                quote!(&::struct_diff::Redacted)
+            } else {
+                quote!((#value).shown())
+            };
+            match field.ident {
+                Some(ref field_name) => {
+                    let field_name_s = field_name.to_string();
+                    tokens.append(quote!(.field(#field_name_s, #shown)))
+                },
+                None => tokens.append(quote!(.field(#shown))),
+            }
         }
     }
 }
 
+/// `fmt_shown` body for a struct or variant called `name` with fields bound to `values`
+fn fmt_shown_fields(name: &syn::Ident, data: &syn::VariantData, values: Vec<quote::Tokens>) -> quote::Tokens {
+    let name_s = name.to_string();
+    match *data {
+        syn::VariantData::Struct(ref fields) => {
+            let gen = ShownFieldsGenerator { fields, values };
+            quote!(f.debug_struct(#name_s)#gen.finish())
+        },
+        syn::VariantData::Tuple(ref fields) => {
+            let gen = ShownFieldsGenerator { fields, values };
+            quote!(f.debug_tuple(#name_s)#gen.finish())
+        },
+        syn::VariantData::Unit => quote!(f.write_str(#name_s)),
+    }
+}
+
 struct StructPatField<'a> {
     name: &'a syn::Ident,
     prefix: &'static str,
@@ -127,9 +438,35 @@ impl<'a> quote::ToTokens for StructPatField<'a> {
     }
 }
 
+/// Generates a short-circuiting `has_diff` expression over fields,
+/// `left` and `right` being references to each of them
+struct HasDiffGenerator<'a> {
+    fields: &'a [syn::Field],
+    pairs: Vec<(quote::Tokens, quote::Tokens)>,
+}
+
+impl<'a> quote::ToTokens for HasDiffGenerator<'a> {
+    fn to_tokens(&self, tokens: &mut quote::Tokens) {
+        if self.pairs.is_empty() {
+            tokens.append("false");
+            return;
+        }
+        for (i, (field, &(ref left, ref right))) in self.fields.iter().zip(self.pairs.iter()).enumerate() {
+            if i > 0 {
+                tokens.append("||");
+            }
+            let check = FieldAttrs::parse(field).has_diff(left, right);
+            tokens.append(quote!(#check));
+        }
+    }
+}
+
 /// Implements Diff for enum
 fn impl_diff_enum(name: &syn::Ident, variants: &[syn::Variant]) -> quote::Tokens {
     let mut differs = Vec::new();
+    let mut checkers = Vec::new();
+    let mut variant_names = Vec::new();
+    let mut shown = Vec::new();
     for variant in variants {
         let var_name = &variant.ident;
         let var_data = &variant.data;
@@ -150,6 +487,14 @@ fn impl_diff_enum(name: &syn::Ident, variants: &[syn::Variant]) -> quote::Tokens
                     new_name.push_str(&ident.to_string());
                     syn::Ident::from(new_name)
                 }).collect::<Vec<_>>();
+                let check = HasDiffGenerator {
+                    fields,
+                    pairs: left.iter().zip(right.iter()).map(|(l, r)| (quote!(#l), quote!(#r))).collect(),
+                };
+                let (left_ref, right_ref) = (&left, &right);
+                checkers.push(quote! {
+                    (&#name::#var_name(#(ref #left_ref),*), &#name::#var_name(#(ref #right_ref),*)) => #check
+                });
                 quote! {
                     (&#name::#var_name(#(ref #left),*), &#name::#var_name(#(ref #right),*)) => {
                        #gen
@@ -168,6 +513,18 @@ fn impl_diff_enum(name: &syn::Ident, variants: &[syn::Variant]) -> quote::Tokens
                 let right = names.iter().map(|ident| {
                     StructPatField{ name: ident, prefix: "right"}
                 }).collect::<Vec<_>>();
+                let check = HasDiffGenerator {
+                    fields,
+                    pairs: names.iter().map(|ident| {
+                        let l = syn::Ident::from(format!("left_{}", ident));
+                        let r = syn::Ident::from(format!("right_{}", ident));
+                        (quote!(#l), quote!(#r))
+                    }).collect(),
+                };
+                let (left_ref, right_ref) = (&left, &right);
+                checkers.push(quote! {
+                    (&#name::#var_name{ #(#left_ref),* }, &#name::#var_name{ #(#right_ref),* }) => #check
+                });
                 quote! {
                     (&#name::#var_name{ #(#left),* }, &#name::#var_name{ #(#right),* }) => {
                        #gen
@@ -175,30 +532,75 @@ fn impl_diff_enum(name: &syn::Ident, variants: &[syn::Variant]) -> quote::Tokens
                 }
             },
             &syn::VariantData::Unit => {
+                checkers.push(quote! {
+                    (&#name::#var_name, &#name::#var_name) => false
+                });
                 quote! {
-                    (&#name::#var_name, &#name::#var_name) => {
-                        return None;
-                    }
+                    (&#name::#var_name, &#name::#var_name) => {}
                 }
             },
         };
         differs.push(diff);
+        let names: Vec<_> = var_data.fields().iter().enumerate().map(|(i, field)| {
+            syn::Ident::from(format!("shown_{}", field.ident.clone().unwrap_or(syn::Ident::from(i))))
+        }).collect();
+        let fmt = fmt_shown_fields(var_name, var_data, names.iter().map(|name| quote!(#name)).collect());
+        shown.push(match *var_data {
+            syn::VariantData::Tuple(_) => quote! { #name::#var_name(#(ref #names),*) => #fmt },
+            syn::VariantData::Struct(ref fields) => {
+                let field_names = fields.iter().map(|field| field.ident.as_ref().unwrap());
+                quote! { #name::#var_name{ #(#field_names: ref #names),* } => #fmt }
+            },
+            syn::VariantData::Unit => quote! { #name::#var_name => #fmt },
+        });
+        let var_name_s = var_name.to_string();
+        variant_names.push(match var_data {
+            &syn::VariantData::Tuple(_) => quote! { #name::#var_name(..) => #var_name_s },
+            &syn::VariantData::Struct(_) => quote! { #name::#var_name{..} => #var_name_s },
+            &syn::VariantData::Unit => quote! { #name::#var_name => #var_name_s },
+        });
     }
     return quote! {
         impl ::struct_diff::Diff for #name {
             type Value = #name;
 
             #[allow(unreachable_patterns)]
-            fn diff<'a>(&'a self, other: &'a #name) -> Option<Vec<::struct_diff::Difference<'a>>> {
-                let mut diffs = Vec::with_capacity(1);
+            fn diff_with<'a, V: ::struct_diff::DiffVisitor<'a> + ?Sized>(&'a self, other: &'a #name, visitor: &mut V) {
                 match (self, other) {
                     #(#differs),*
                     _ => {
-                        diffs.push(::struct_diff::Difference { field: "self".into(), left: self, right: other });
+                        let variant_name = |value: &#name| match *value {
+                            #(#variant_names),*
+                        };
+                        visitor.leaf(self.shown(), other.shown(), ::struct_diff::ChangeKind::VariantChanged {
+                            from: variant_name(self),
+                            to: variant_name(other),
+                        });
                     }
                 }
-                if diffs.len() > 0 {
-                    return Some(diffs);
+            }
+
+            #[allow(unreachable_patterns)]
+            fn has_diff(&self, other: &#name) -> bool {
+                match (self, other) {
+                    #(#checkers,)*
+                    _ => true,
+                }
+            }
+
+            fn fmt_shown(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                match *self {
+                    #(#shown),*
+                }
+            }
+        }
+
+        impl<'a> ::struct_diff::Delta<'a> for #name {
+            type Delta = ::struct_diff::Change<'a, #name>;
+
+            fn delta(&'a self, other: &'a #name) -> ::std::option::Option<::struct_diff::Change<'a, #name>> {
+                if ::struct_diff::Diff::has_diff(self, other) {
+                    return Some(::struct_diff::Change { from: self, to: other });
                 }
                 None
             }
@@ -213,28 +615,232 @@ struct TupleFieldsGenerator<'a> {
 
 impl<'a> quote::ToTokens for TupleFieldsGenerator<'a> {
     fn to_tokens(&self, tokens: &mut quote::Tokens) {
-        for (field_name, _) in self.fields.iter().enumerate() {
+        for (field_name, field) in self.fields.iter().enumerate() {
                 let field_name_s = field_name.to_string();
                 use quote::Ident;
                 let field_name = Ident::new(format!("{}", field_name));
-                tokens.append(
-                    quote!{
-                        if let Some(inner_diffs) = self.#field_name.diff(&other.#field_name) {
-                            for diff in inner_diffs {
-                                let mut path = String::from(#field_name_s);
-                                if !diff.field.is_empty() {
-                                    path.push_str(&".");
-                                }
-                                path.push_str(&diff.field);
-                                diffs.push(::struct_diff::Difference {
-                                    field: path,
-                                    left: diff.left,
-                                    right: diff.right,
-                                })
-                            }
-                        }
+                let visit = VisitGenerator {
+                    segment: quote!(::struct_diff::PathSegment::Field(#field_name_s)),
+                    left: quote!(&self.#field_name),
+                    right: quote!(&other.#field_name),
+                    attrs: FieldAttrs::parse(field),
+                };
+                tokens.append(quote!(#visit));
+        }
+    }
+}
+
+/// Generates `FooDelta` and implements Delta for structs
+fn impl_delta(name: &syn::Ident, vis: &syn::Visibility, fields: &[syn::Field]) -> quote::Tokens {
+    let delta_name = syn::Ident::from(format!("{}Delta", name));
+    let doc = format!("Typed changes between two `{}`, see `struct_diff::Delta`", name);
+    let mut types = Vec::new();
+    let mut values = Vec::new();
+    for field in fields {
+        let field_name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let attrs = FieldAttrs::parse(field);
+        let (left, right) = (quote!(&self.#field_name), quote!(&other.#field_name));
+        if attrs.redact {
+            let check = attrs.has_diff(&left, &right);
+            types.push(quote!(::std::option::Option<::struct_diff::Change<'a, ::struct_diff::Redacted>>));
+            values.push(quote! {
+                if #check {
+                    Some(::struct_diff::Change { from: &::struct_diff::Redacted, to: &::struct_diff::Redacted })
+                } else {
+                    None
+                }
+            });
+        } else if attrs.key.is_some() || attrs.unordered {
+            let check = attrs.has_diff(&left, &right);
+            types.push(quote!(::std::option::Option<::struct_diff::Change<'a, #ty>>));
+            values.push(quote! {
+                if #check {
+                    Some(::struct_diff::Change { from: #left, to: #right })
+                } else {
+                    None
+                }
+            });
+        } else {
+            types.push(quote!(::std::option::Option<<#ty as ::struct_diff::Delta<'a>>::Delta>));
+            values.push(quote!(::struct_diff::Delta::delta(#left, #right)));
+        }
+    }
+    let names: Vec<_> = fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+    let (names_1, names_2, names_3) = (&names, &names, &names);
+    quote! {
+        #[doc = #doc]
+        #[derive(Debug, PartialEq)]
+        #vis struct #delta_name<'a> {
+            #(pub #names_1: #types),*
+        }
+
+        impl<'a> ::struct_diff::Delta<'a> for #name {
+            type Delta = #delta_name<'a>;
+
+            fn delta(&'a self, other: &'a #name) -> ::std::option::Option<#delta_name<'a>> {
+                let delta = #delta_name {
+                    #(#names_2: #values),*
+                };
+                if #(delta.#names_3.is_none())&&* {
+                    return None;
+                }
+                Some(delta)
+            }
+        }
+    }
+}
+
+/// Generates `FooPatch` and implements Patch for structs
+fn impl_patch(name: &syn::Ident, vis: &syn::Visibility, fields: &[syn::Field]) -> quote::Tokens {
+    let patch_name = syn::Ident::from(format!("{}Patch", name));
+    let doc = format!("Changeset bringing a `{}` to another state, see `struct_diff::Patch`", name);
+    let patch_name_s = patch_name.to_string();
+    let names: Vec<_> = fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let mut shown = Vec::new();
+    for field in fields {
+        let field_name = field.ident.as_ref().unwrap();
+        let field_name_s = field_name.to_string();
+        let ty = &field.ty;
+        shown.push(if FieldAttrs::parse(field).redact {
+            quote!(.field(#field_name_s, &self.#field_name.as_ref().map(|_| ::struct_diff::Redacted)))
+        } else {
+            quote!(.field(#field_name_s, &self.#field_name.as_ref().map(<#ty as ::struct_diff::Patch>::shown_patch)))
+        });
+    }
+    let (names_1, names_2, names_3, names_4, names_5) = (&names, &names, &names, &names, &names);
+    let (names_6, names_7) = (&names, &names);
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, PartialEq)]
+        #vis struct #patch_name {
+            #(pub #names_1: ::std::option::Option<<#types as ::struct_diff::Patch>::Patch>),*
+        }
+
+        impl ::std::fmt::Debug for #patch_name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                f.debug_struct(#patch_name_s)#(#shown)*.finish()
+            }
+        }
+
+        impl ::struct_diff::Patch for #name {
+            type Patch = #patch_name;
+
+            fn patch(&self, other: &#name) -> ::std::option::Option<#patch_name> {
+                let patch = #patch_name {
+                    #(#names_2: ::struct_diff::Patch::patch(&self.#names_3, &other.#names_4)),*
+                };
+                if #(patch.#names_5.is_none())&&* {
+                    return None;
+                }
+                Some(patch)
+            }
+
+            fn apply(&mut self, patch: &#patch_name) {
+                #(
+                    if let Some(ref field) = patch.#names_6 {
+                        ::struct_diff::Patch::apply(&mut self.#names_7, field);
                     }
-                );
+                )*
+            }
+        }
+    }
+}
+
+/// Implements Patch for enums, replacing them as a whole
+fn impl_patch_enum(name: &syn::Ident) -> quote::Tokens {
+    quote! {
+        impl ::struct_diff::Patch for #name {
+            type Patch = #name;
+
+            fn patch(&self, other: &#name) -> ::std::option::Option<#name> {
+                if ::struct_diff::Diff::has_diff(self, other) {
+                    return Some(::std::clone::Clone::clone(other));
+                }
+                None
+            }
+
+            fn apply(&mut self, patch: &#name) {
+                *self = ::std::clone::Clone::clone(patch);
+            }
+
+            fn shown_patch(patch: &#name) -> &::std::fmt::Debug {
+                ::struct_diff::Diff::shown(patch)
+            }
+        }
+    }
+}
+
+/// Generates `FooField` and `Foo::changed_fields`
+fn impl_changed_fields(name: &syn::Ident, vis: &syn::Visibility, fields: &[syn::Field]) -> quote::Tokens {
+    if fields.len() > 64 {
+        panic!("#[diff(fields)] supports at most 64 fields, {} has {}", name, fields.len());
+    }
+    let field_enum = syn::Ident::from(format!("{}Field", name));
+    let doc = format!("Fields of `{}`, see `struct_diff::FieldSet`", name);
+    let names: Vec<_> = fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+    let names_s: Vec<_> = names.iter().map(|name| name.to_string()).collect();
+    let variants: Vec<_> = names_s.iter().map(|name| {
+        let camel: String = name.split('_').map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }).collect();
+        syn::Ident::from(camel)
+    }).collect();
+    let indices: Vec<_> = (0..fields.len()).collect();
+    let checks: Vec<_> = fields.iter().zip(names.iter()).map(|(field, field_name)| {
+        FieldAttrs::parse(field).has_diff(&quote!(&self.#field_name), &quote!(&other.#field_name))
+    }).collect();
+    let columns: Vec<_> = fields.iter().zip(names_s.iter()).map(|(field, name)| {
+        FieldAttrs::parse(field).column.unwrap_or_else(|| name.clone())
+    }).collect();
+    let (variants_1, variants_2, variants_3, variants_4, variants_5) = (&variants, &variants, &variants, &variants, &variants);
+    let field_enum_rep = std::iter::repeat(&field_enum).take(fields.len()).collect::<Vec<_>>();
+    let (field_enum_1, field_enum_2, field_enum_3, field_enum_4, field_enum_5) = (&field_enum_rep, &field_enum_rep, &field_enum_rep, &field_enum_rep, &field_enum_rep);
+    quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #vis enum #field_enum {
+            #(#variants_1),*
+        }
+
+        impl ::struct_diff::FieldEnum for #field_enum {
+            const ALL: &'static [#field_enum] = &[#(#field_enum_1::#variants_2),*];
+
+            fn index(self) -> usize {
+                match self {
+                    #(#field_enum_2::#variants_3 => #indices),*
+                }
+            }
+
+            fn name(self) -> &'static str {
+                match self {
+                    #(#field_enum_3::#variants_4 => #names_s),*
+                }
+            }
+
+            fn column(self) -> &'static str {
+                match self {
+                    #(#field_enum_5::#variants_5 => #columns),*
+                }
+            }
+        }
+
+        impl #name {
+            /// Fields that differ from `other`, stopping at the first difference of each
+            pub fn changed_fields(&self, other: &#name) -> ::struct_diff::FieldSet<#field_enum> {
+                let mut changed = ::struct_diff::FieldSet::new();
+                #(
+                    if #checks {
+                        changed.insert(#field_enum_4::#variants);
+                    }
+                )*
+                changed
+            }
         }
     }
 }
@@ -244,34 +850,60 @@ fn impl_diff_struct(name: &syn::Ident, struct_: &syn::VariantData) -> quote::Tok
     match struct_ {
         &syn::VariantData::Struct(ref fields) => {
             let gen = StructGenerator { fields };
+            let check = HasDiffGenerator {
+                fields,
+                pairs: fields.iter().filter_map(|field| field.ident.as_ref()).map(|field_name| {
+                    (quote!(&self.#field_name), quote!(&other.#field_name))
+                }).collect(),
+            };
+            let fmt = fmt_shown_fields(name, struct_, fields.iter().filter_map(|field| field.ident.as_ref()).map(|field_name| {
+                quote!(&self.#field_name)
+            }).collect());
             return quote! {
                 impl ::struct_diff::Diff for #name {
                     type Value = #name;
 
-                    fn diff<'a>(&'a self, other: &'a #name) -> Option<Vec<::struct_diff::Difference<'a>>> {
-                        let mut diffs = Vec::new();
+                    fn diff_with<'a, V: ::struct_diff::DiffVisitor<'a> + ?Sized>(&'a self, other: &'a #name, visitor: &mut V) {
                         #gen
-                        if diffs.len() > 0 {
-                            return Some(diffs);
-                        }
-                        return None;
+                    }
+
+                    fn has_diff(&self, other: &#name) -> bool {
+                        #check
+                    }
+
+                    fn fmt_shown(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                        #fmt
                     }
                 }
             }
         },
         &syn::VariantData::Tuple(ref fields) => {
             let gen = TupleFieldsGenerator { fields };
+            let check = HasDiffGenerator {
+                fields,
+                pairs: (0..fields.len()).map(|i| {
+                    let field_name = quote::Ident::new(format!("{}", i));
+                    (quote!(&self.#field_name), quote!(&other.#field_name))
+                }).collect(),
+            };
+            let fmt = fmt_shown_fields(name, struct_, (0..fields.len()).map(|i| {
+                let field_name = quote::Ident::new(format!("{}", i));
+                quote!(&self.#field_name)
+            }).collect());
             return quote! {
                 impl ::struct_diff::Diff for #name {
                     type Value = #name;
 
-                    fn diff<'a>(&'a self, other: &'a #name) -> Option<Vec<::struct_diff::Difference<'a>>> {
-                        let mut diffs = Vec::new();
+                    fn diff_with<'a, V: ::struct_diff::DiffVisitor<'a> + ?Sized>(&'a self, other: &'a #name, visitor: &mut V) {
                         #gen
-                        if diffs.len() > 0 {
-                            return Some(diffs);
-                        }
-                        return None;
+                    }
+
+                    fn has_diff(&self, other: &#name) -> bool {
+                        #check
+                    }
+
+                    fn fmt_shown(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                        #fmt
                     }
                 }
             }
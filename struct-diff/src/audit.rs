@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+
+use {Diff, OwnedDifference};
+
+/// Identifies a value in audit records, derived for structs
+/// with a field marked `#[diff(entity_id)]`
+pub trait Entity {
+    /// Kind of entity, the type name when derived
+    fn entity_name(&self) -> &'static str;
+
+    /// Identifier of this particular entity, rendered with `Display` when derived
+    fn entity_id(&self) -> String;
+}
+
+/// Who changed which entity when, and how.
+///
+/// Serializes `at` as RFC 3339 and `kind` of every change
+/// as `ChangeKind::name`, or `{"variant_changed": {"from": .., "to": ..}}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub entity: String,
+    pub entity_id: String,
+    pub actor: String,
+    pub at: DateTime<Utc>,
+    pub changes: Vec<OwnedDifference>,
+}
+
+impl AuditRecord {
+    /// Diffs `before` and `after`, stamped with the current time.
+    /// The entity is identified by `after`. Returns `None` if nothing changed.
+    pub fn new<T>(actor: &str, before: &T, after: &T) -> Option<Self>
+        where T: Diff<Value=T> + Entity
+    {
+        let changes = before.diff(after)?;
+        Some(AuditRecord {
+            entity: after.entity_name().to_owned(),
+            entity_id: after.entity_id(),
+            actor: actor.to_owned(),
+            at: Utc::now(),
+            changes: changes.into_iter().map(|diff| diff.into_owned()).collect(),
+        })
+    }
+
+    /// Replaces the time the change is recorded at
+    pub fn at(mut self, at: DateTime<Utc>) -> Self {
+        self.at = at;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use ChangeKind;
+
+    struct User {
+        id: u32,
+        name: String,
+    }
+
+    impl Diff for User {
+        type Value = User;
+
+        fn diff_with<'a, V: ::DiffVisitor<'a> + ?Sized>(&'a self, other: &'a User, visitor: &mut V) {
+            if visitor.enter_field(::PathSegment::Field("name")) {
+                self.name.diff_with(&other.name, visitor);
+                visitor.leave_field();
+            }
+        }
+    }
+
+    impl Entity for User {
+        fn entity_name(&self) -> &'static str {
+            "User"
+        }
+
+        fn entity_id(&self) -> String {
+            self.id.to_string()
+        }
+    }
+
+    impl ::std::fmt::Debug for User {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            f.write_str(&self.name)
+        }
+    }
+
+    impl PartialEq for User {
+        fn eq(&self, other: &User) -> bool {
+            self.id == other.id && self.name == other.name
+        }
+    }
+
+    #[test]
+    fn test_audit_record() {
+        let before = User { id: 7, name: "a".into() };
+        let after = User { id: 7, name: "b".into() };
+        let at = Utc.timestamp_opt(0, 0).unwrap();
+        let record = AuditRecord::new("admin", &before, &after).unwrap().at(at);
+        assert_eq!(record, AuditRecord {
+            entity: "User".into(),
+            entity_id: "7".into(),
+            actor: "admin".into(),
+            at,
+            changes: vec![OwnedDifference {
+                field: "name".into(),
+                kind: ChangeKind::Modified,
+                left: "\"a\"".into(),
+                right: "\"b\"".into(),
+            }],
+        });
+        assert!(AuditRecord::new("admin", &before, &before).is_none());
+    }
+}
@@ -0,0 +1,108 @@
+use {ChangeKind, OwnedDifference};
+
+/// Composes the differences of `A -> B` and `B -> C` into those of `A -> C`,
+/// dropping changes that cancel out.
+///
+/// Changes are matched by path. A change is merged with the latest one of the
+/// same path if it starts where that one ended, keeping `left` of the first and
+/// `right` of the second, and dropped when both sides end up equal. Otherwise
+/// the differences don't follow each other, and both changes are kept in order.
+/// A path and one of its ancestors, e.g. `a` replaced as a whole
+/// and `a.b` modified, can't be merged from rendered values either.
+///
+/// Changes of `first` keep their position, those only in `second` follow.
+pub fn compose(first: &[OwnedDifference], second: &[OwnedDifference]) -> Vec<OwnedDifference> {
+    let mut composed = first.to_vec();
+    for change in second {
+        match composed.iter().rposition(|prev| prev.field == change.field) {
+            Some(i) if composed[i].right == change.left => {
+                if composed[i].left == change.right {
+                    composed.remove(i);
+                } else {
+                    composed[i].kind = compose_kind(composed[i].kind, change.kind);
+                    composed[i].right = change.right.clone();
+                }
+            }
+            _ => composed.push(change.clone()),
+        }
+    }
+    composed
+}
+
+fn compose_kind(first: ChangeKind, second: ChangeKind) -> ChangeKind {
+    match (first, second) {
+        (ChangeKind::Truncated, _) | (_, ChangeKind::Truncated) => ChangeKind::Truncated,
+        (ChangeKind::Removed, ChangeKind::Added) => ChangeKind::Modified,
+        (ChangeKind::Added, _) => ChangeKind::Added,
+        (_, ChangeKind::Removed) => ChangeKind::Removed,
+        (ChangeKind::VariantChanged { from, .. }, ChangeKind::VariantChanged { to, .. }) => {
+            if from == to {
+                ChangeKind::Modified
+            } else {
+                ChangeKind::VariantChanged { from, to }
+            }
+        }
+        (kind @ ChangeKind::VariantChanged { .. }, _) | (_, kind @ ChangeKind::VariantChanged { .. }) => kind,
+        _ => ChangeKind::Modified,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn change(field: &str, kind: ChangeKind, left: &str, right: &str) -> OwnedDifference {
+        OwnedDifference { field: field.into(), kind, left: left.into(), right: right.into() }
+    }
+
+    #[test]
+    fn test_compose() {
+        let first = vec![
+            change("a", ChangeKind::Modified, "1", "2"),
+            change("b", ChangeKind::Added, "None", "Some(1)"),
+            change("c", ChangeKind::Modified, "1", "2"),
+            change("d", ChangeKind::VariantChanged { from: "X", to: "Y" }, "X", "Y(1)"),
+        ];
+        let second = vec![
+            change("a", ChangeKind::Modified, "2", "1"),
+            change("b", ChangeKind::Removed, "Some(1)", "None"),
+            change("c", ChangeKind::Modified, "2", "3"),
+            change("d", ChangeKind::VariantChanged { from: "Y", to: "Z" }, "Y(1)", "Z"),
+            change("e", ChangeKind::Removed, "Some(1)", "None"),
+        ];
+        assert_eq!(compose(&first, &second), vec![
+            change("c", ChangeKind::Modified, "1", "3"),
+            change("d", ChangeKind::VariantChanged { from: "X", to: "Z" }, "X", "Z"),
+            change("e", ChangeKind::Removed, "Some(1)", "None"),
+        ]);
+        assert_eq!(compose(&first, &[]), first);
+    }
+
+    #[test]
+    fn test_compose_repeated() {
+        let first = vec![
+            change("a", ChangeKind::Modified, "1", "2"),
+            change("b", ChangeKind::Modified, "1", "2"),
+        ];
+        let second = vec![
+            change("a", ChangeKind::Modified, "5", "6"),
+            change("a", ChangeKind::Modified, "6", "7"),
+            change("b", ChangeKind::Modified, "2", "3"),
+            change("b", ChangeKind::Modified, "3", "1"),
+        ];
+        assert_eq!(compose(&first, &second), vec![
+            change("a", ChangeKind::Modified, "1", "2"),
+            change("a", ChangeKind::Modified, "5", "7"),
+        ]);
+    }
+
+    #[test]
+    fn test_compose_kind() {
+        assert_eq!(compose_kind(ChangeKind::Removed, ChangeKind::Added), ChangeKind::Modified);
+        assert_eq!(compose_kind(ChangeKind::Added, ChangeKind::Modified), ChangeKind::Added);
+        assert_eq!(compose_kind(ChangeKind::Modified, ChangeKind::Removed), ChangeKind::Removed);
+        let variant = ChangeKind::VariantChanged { from: "X", to: "Y" };
+        assert_eq!(compose_kind(variant, ChangeKind::Modified), variant);
+        assert_eq!(compose_kind(variant, ChangeKind::VariantChanged { from: "Y", to: "X" }), ChangeKind::Modified);
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt::{self, Debug};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use chrono::{self, DateTime};
+
+use Diff;
+
+/// Typed changeset between two values, generated for structs
+/// with `#[diff(delta)]` as a `FooDelta` with an optional delta per field.
+///
+/// Values without fields of their own change as a whole, see `Change`.
+pub trait Delta<'a> {
+    type Delta: Debug + PartialEq;
+
+    /// Returns `None` if `diff` would report no difference
+    fn delta(&'a self, other: &'a Self) -> Option<Self::Delta>;
+}
+
+/// Value that changed as a whole.
+/// `Debug` shows both sides like `Diff::fmt_shown`, hiding `#[diff(redact)]` fields.
+#[derive(Clone, PartialEq)]
+pub struct Change<'a, T: ?Sized + 'a> {
+    pub from: &'a T,
+    pub to: &'a T,
+}
+
+impl<'a, T: Diff + Debug + ?Sized> Debug for Change<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Change")
+            .field("from", &self.from.shown())
+            .field("to", &self.to.shown())
+            .finish()
+    }
+}
+
+macro_rules! impl_delta_as_change {
+    ($($t: ty),*) => {
+        $(
+            impl<'a> Delta<'a> for $t {
+                type Delta = Change<'a, $t>;
+
+                fn delta(&'a self, other: &'a Self) -> Option<Self::Delta> {
+                    if self.has_diff(other) {
+                        return Some(Change { from: self, to: other });
+                    }
+                    None
+                }
+            }
+        )*
+    };
+}
+
+impl_delta_as_change!(bool, isize, i8, i16, i32, i64, usize, u8, u16, u32, u64, f32, f64, char, String,
+                      chrono::NaiveDateTime, PathBuf);
+
+macro_rules! impl_delta_for_ref {
+    ($($t: ty),*) => {
+        $(
+            impl<'a, 'b: 'a> Delta<'a> for &'b $t {
+                type Delta = Change<'a, &'b $t>;
+
+                fn delta(&'a self, other: &'a Self) -> Option<Self::Delta> {
+                    if self.has_diff(other) {
+                        return Some(Change { from: self, to: other });
+                    }
+                    None
+                }
+            }
+        )*
+    };
+}
+
+impl_delta_for_ref!(str, Path);
+
+macro_rules! impl_delta_for_generic {
+    ($($t: ty),*) => {
+        $(
+            impl<'a, T> Delta<'a> for $t where T: Debug + PartialEq + Diff<Value=T> + 'a {
+                type Delta = Change<'a, $t>;
+
+                fn delta(&'a self, other: &'a Self) -> Option<Self::Delta> {
+                    if self[..].has_diff(&other[..]) {
+                        return Some(Change { from: self, to: other });
+                    }
+                    None
+                }
+            }
+        )*
+    };
+}
+
+impl_delta_for_generic!([T], Vec<T>);
+
+macro_rules! impl_delta_for_wrapper {
+    ($($t: ident),*) => {
+        $(
+            impl<'a, T> Delta<'a> for $t<T> where T: Debug + PartialEq + Diff<Value=T> + 'a {
+                type Delta = Change<'a, $t<T>>;
+
+                fn delta(&'a self, other: &'a Self) -> Option<Self::Delta> {
+                    if (**self).has_diff(&**other) {
+                        return Some(Change { from: self, to: other });
+                    }
+                    None
+                }
+            }
+        )*
+    };
+}
+
+impl_delta_for_wrapper!(Box, Rc, Arc);
+
+impl<'a, T> Delta<'a> for Option<T> where T: Debug + PartialEq + Diff<Value=T> + 'a {
+    type Delta = Change<'a, Option<T>>;
+
+    fn delta(&'a self, other: &'a Self) -> Option<Self::Delta> {
+        if self.has_diff(other) {
+            return Some(Change { from: self, to: other });
+        }
+        None
+    }
+}
+
+impl<'a, T: chrono::TimeZone + 'a> Delta<'a> for DateTime<T> {
+    type Delta = Change<'a, DateTime<T>>;
+
+    fn delta(&'a self, other: &'a Self) -> Option<Self::Delta> {
+        if self.has_diff(other) {
+            return Some(Change { from: self, to: other });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delta() {
+        assert_eq!(1.delta(&2), Some(Change { from: &1, to: &2 }));
+        assert_eq!(1.delta(&1), None);
+        assert_eq!(vec![1, 2].delta(&vec![1, 3]).map(|change| change.to), Some(&vec![1, 3]));
+        assert_eq!(Some("a".to_owned()).delta(&None).map(|change| change.to), Some(&None));
+        assert!(Box::new(1).delta(&Box::new(1)).is_none());
+    }
+}
@@ -0,0 +1,80 @@
+//! `Diff` for dynamic documents: objects are walked by key, arrays by index.
+//! Keys and elements present on one side only are reported as
+//! `ChangeKind::Added` or `ChangeKind::Removed`, with `<missing>` on the other side.
+
+use {diff_entry, ChangeKind, Diff, DiffVisitor, PathSegment};
+
+macro_rules! impl_for_document {
+    ($t: ty, $object: path, $array: path) => {
+        impl Diff for $t {
+            type Value = $t;
+
+            fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+                match (self, other) {
+                    (&$object(ref left), &$object(ref right)) => {
+                        for (key, value) in left.iter() {
+                            diff_entry(PathSegment::Field(key), Some(value), right.get(key), visitor);
+                        }
+                        for (key, value) in right.iter().filter(|&(key, _)| !left.contains_key(key)) {
+                            diff_entry(PathSegment::Field(key), None, Some(value), visitor);
+                        }
+                    },
+                    (&$array(ref left), &$array(ref right)) => {
+                        for i in 0..left.len().max(right.len()) {
+                            diff_entry(PathSegment::Index(i), left.get(i), right.get(i), visitor);
+                        }
+                    },
+                    _ => {
+                        if self.ne(other) {
+                            visitor.leaf(self, other, ChangeKind::Modified);
+                        }
+                    },
+                }
+            }
+
+            fn has_diff(&self, other: &Self::Value) -> bool {
+                self.ne(other)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "serde_json")]
+impl_for_document!(::serde_json::Value, ::serde_json::Value::Object, ::serde_json::Value::Array);
+
+#[cfg(feature = "toml")]
+impl_for_document!(::toml::Value, ::toml::Value::Table, ::toml::Value::Array);
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json() {
+        use ChangeKind;
+        use Diff;
+
+        let a = json!({"name": "a", "tags": ["x", "y"], "owner": {"id": 1, "email": "a@b"}});
+        let b = json!({"name": "b", "tags": ["x"], "owner": {"id": 1}, "size": 3});
+        let diffs = a.diff(&b).unwrap();
+        let rendered: Vec<_> = diffs.iter().map(|d| (d.field.as_str(), d.kind, format!("{}", d))).collect();
+        assert_eq!(rendered, vec![
+            ("name", ChangeKind::Modified, "name: String(\"a\") -> String(\"b\")".to_owned()),
+            ("owner.email", ChangeKind::Removed, "owner.email: String(\"a@b\") -> <missing>".to_owned()),
+            ("tags.[1]", ChangeKind::Removed, "tags.[1]: String(\"y\") -> <missing>".to_owned()),
+            ("size", ChangeKind::Added, "size: <missing> -> Number(3)".to_owned()),
+        ]);
+        assert!(json!([1]).diff(&json!({"a": 1})).unwrap()[0].field.is_empty());
+        assert!(!a.has_diff(&a.clone()));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml() {
+        use Diff;
+
+        let a: ::toml::Value = "[server]\nport = 80\nhosts = [\"a\"]".parse().unwrap();
+        let b: ::toml::Value = "[server]\nport = 81\nhosts = [\"a\", \"b\"]".parse().unwrap();
+        let fields: Vec<_> = a.diff(&b).unwrap().into_iter().map(|d| d.field).collect();
+        assert_eq!(fields, vec!["server.hosts.[1]", "server.port"]);
+    }
+}
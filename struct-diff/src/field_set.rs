@@ -0,0 +1,163 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Enum of the fields of a struct, generated as `FooField` with `#[diff(fields)]`
+pub trait FieldEnum: Copy + 'static {
+    /// All fields, in declaration order
+    const ALL: &'static [Self];
+
+    /// Position of the field in `ALL`, below 64
+    fn index(self) -> usize;
+
+    /// Name of the field as declared
+    fn name(self) -> &'static str;
+
+    /// Database column the field is stored in, set with `#[diff(column = "..")]`
+    fn column(self) -> &'static str {
+        self.name()
+    }
+}
+
+/// Set of fields of a struct, e.g. those returned by a generated `changed_fields`.
+/// Kept as a bitmask, so it never allocates.
+pub struct FieldSet<F> {
+    bits: u64,
+    marker: PhantomData<F>,
+}
+
+impl<F: FieldEnum> FieldSet<F> {
+    pub fn new() -> Self {
+        FieldSet { bits: 0, marker: PhantomData }
+    }
+
+    pub fn insert(&mut self, field: F) {
+        self.bits |= 1 << field.index();
+    }
+
+    pub fn remove(&mut self, field: F) {
+        self.bits &= !(1 << field.index());
+    }
+
+    pub fn contains(&self, field: F) -> bool {
+        self.bits & (1 << field.index()) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    /// Fields in the set, in declaration order
+    pub fn iter(&self) -> FieldSetIter<F> {
+        FieldSetIter { bits: self.bits, all: F::ALL.iter() }
+    }
+}
+
+impl<F: FieldEnum> Default for FieldSet<F> {
+    fn default() -> Self {
+        FieldSet::new()
+    }
+}
+
+impl<F> Clone for FieldSet<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for FieldSet<F> {}
+
+impl<F> PartialEq for FieldSet<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
+    }
+}
+
+impl<F> Eq for FieldSet<F> {}
+
+/// Renders as the set of field names, e.g. `{"name", "price"}`
+impl<F: FieldEnum> fmt::Debug for FieldSet<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter().map(|field| field.name())).finish()
+    }
+}
+
+impl<F: FieldEnum> ::std::iter::FromIterator<F> for FieldSet<F> {
+    fn from_iter<I: IntoIterator<Item=F>>(iter: I) -> Self {
+        let mut set = FieldSet::new();
+        for field in iter {
+            set.insert(field);
+        }
+        set
+    }
+}
+
+impl<F: FieldEnum> IntoIterator for &FieldSet<F> {
+    type Item = F;
+    type IntoIter = FieldSetIter<F>;
+
+    fn into_iter(self) -> FieldSetIter<F> {
+        self.iter()
+    }
+}
+
+/// Iterator over the fields of a `FieldSet`
+pub struct FieldSetIter<F: 'static> {
+    bits: u64,
+    all: ::std::slice::Iter<'static, F>,
+}
+
+impl<F: FieldEnum> Iterator for FieldSetIter<F> {
+    type Item = F;
+
+    fn next(&mut self) -> Option<F> {
+        let bits = self.bits;
+        self.all.by_ref().cloned().find(|field| bits & (1 << field.index()) != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Field {
+        A,
+        B,
+        C,
+    }
+
+    impl FieldEnum for Field {
+        const ALL: &'static [Field] = &[Field::A, Field::B, Field::C];
+
+        fn index(self) -> usize {
+            self as usize
+        }
+
+        fn name(self) -> &'static str {
+            match self {
+                Field::A => "a",
+                Field::B => "b",
+                Field::C => "c",
+            }
+        }
+    }
+
+    #[test]
+    fn test_field_set() {
+        let mut set = FieldSet::new();
+        assert!(set.is_empty());
+        set.insert(Field::C);
+        set.insert(Field::A);
+        assert!(set.contains(Field::A));
+        assert!(!set.contains(Field::B));
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![Field::A, Field::C]);
+        assert_eq!(format!("{:?}", set), r#"{"a", "c"}"#);
+        set.remove(Field::A);
+        assert_eq!(set, vec![Field::C].into_iter().collect());
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use {Diff, OwnedDifference, Patch};
+
+/// Changes recorded by `History` for one version,
+/// `P` being the `Patch` of the recorded value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Revision<P> {
+    /// Version the changes lead to, the initial state being version 0
+    pub version: usize,
+    pub at: DateTime<Utc>,
+    pub changes: Vec<OwnedDifference>,
+    /// Brings this version back to the previous one, see `History::at`
+    pub undo: P,
+}
+
+impl<P> Revision<P> {
+    /// Whether the revision changed `path`, something below it,
+    /// or a value containing it that was reported as a whole
+    pub fn touches(&self, path: &str) -> bool {
+        self.changes.iter().any(|change| is_prefix(path, &change.field) || is_prefix(&change.field, path))
+    }
+}
+
+/// `prefix` is `path` itself or one of its ancestors
+fn is_prefix(prefix: &str, path: &str) -> bool {
+    prefix.is_empty() || path == prefix || (path.starts_with(prefix) && path[prefix.len()..].starts_with('.'))
+}
+
+/// Where `History` keeps its revisions, undo patches included
+pub trait RevisionStore<P> {
+    fn push(&mut self, revision: Revision<P>);
+
+    /// Revisions from the oldest to the newest
+    fn revisions<'s>(&'s self) -> Box<dyn DoubleEndedIterator<Item=&'s Revision<P>> + 's>;
+}
+
+/// Keeps revisions in memory
+#[derive(Debug, Clone)]
+pub struct MemoryStore<P>(Vec<Revision<P>>);
+
+impl<P> Default for MemoryStore<P> {
+    fn default() -> Self {
+        MemoryStore(Vec::new())
+    }
+}
+
+impl<P> RevisionStore<P> for MemoryStore<P> {
+    fn push(&mut self, revision: Revision<P>) {
+        self.0.push(revision);
+    }
+
+    fn revisions<'s>(&'s self) -> Box<dyn DoubleEndedIterator<Item=&'s Revision<P>> + 's> {
+        Box::new(self.0.iter())
+    }
+}
+
+/// Records successive states of a value as differences, keeping only the current one.
+///
+/// What was changed, and when, is kept for every path, e.g. for per-field blame.
+/// Every revision also holds the `Patch` back to the previous version, which `at`
+/// applies from the current state to rebuild past ones, whatever the store.
+///
+/// ```
+/// use struct_diff::History;
+///
+/// let mut history = History::new(vec![80, 443]);
+/// history.record(vec![81, 443]);
+/// history.record(vec![81]);
+/// assert_eq!(history.version(), 2);
+/// assert_eq!(history.last_changed("[0]").unwrap().version, 1);
+/// assert_eq!(history.at(1), vec![81, 443]);
+/// assert_eq!(history.at(0), vec![80, 443]);
+/// ```
+pub struct History<T: Patch, S = MemoryStore<<T as Patch>::Patch>> {
+    current: T,
+    version: usize,
+    store: S,
+}
+
+impl<T: Diff<Value=T> + Patch> History<T, MemoryStore<T::Patch>> {
+    pub fn new(initial: T) -> Self {
+        History::with_store(initial, MemoryStore::default())
+    }
+}
+
+impl<T: Diff<Value=T> + Patch, S: RevisionStore<T::Patch>> History<T, S> {
+    /// Resumes from the revisions already in `store`, `current` being the state
+    /// they lead to. With an empty store, `current` is version 0.
+    pub fn with_store(current: T, store: S) -> Self {
+        let version = store.revisions().next_back().map_or(0, |revision| revision.version);
+        History { current, version, store }
+    }
+
+    /// Records `next` as the new state, stamped with the current time.
+    /// Returns its version, or `None` if it doesn't differ from the current state.
+    pub fn record(&mut self, next: T) -> Option<usize> {
+        self.record_at(next, Utc::now())
+    }
+
+    /// Same as `record`, with the time of the change given
+    pub fn record_at(&mut self, next: T, at: DateTime<Utc>) -> Option<usize> {
+        let changes: Vec<_> = match self.current.diff(&next) {
+            Some(diffs) => diffs.into_iter().map(|diff| diff.into_owned()).collect(),
+            None => return None,
+        };
+        let undo = next.patch(&self.current)?;
+        self.version += 1;
+        self.store.push(Revision { version: self.version, at, changes, undo });
+        self.current = next;
+        Some(self.version)
+    }
+
+    pub fn current(&self) -> &T {
+        &self.current
+    }
+
+    /// State at `version`, rebuilt from the current one.
+    ///
+    /// Panics if `version` is past the current one.
+    pub fn at(&self, version: usize) -> T where T: Clone {
+        assert!(version <= self.version, "version {} is past the current one, {}", version, self.version);
+        let mut state = self.current.clone();
+        for revision in self.store.revisions().rev().take_while(|revision| revision.version > version) {
+            state.apply(&revision.undo);
+        }
+        state
+    }
+
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn revisions<'s>(&'s self) -> Box<dyn DoubleEndedIterator<Item=&'s Revision<T::Patch>> + 's> {
+        self.store.revisions()
+    }
+
+    /// Latest revision that changed `path`, see `Revision::touches`
+    pub fn last_changed(&self, path: &str) -> Option<&Revision<T::Patch>> {
+        self.store.revisions().rev().find(|revision| revision.touches(path))
+    }
+
+    /// Latest revision for every path changed so far
+    pub fn blame(&self) -> BTreeMap<&str, &Revision<T::Patch>> {
+        let mut blame = BTreeMap::new();
+        for revision in self.store.revisions() {
+            for change in revision.changes.iter() {
+                blame.insert(change.field.as_str(), revision);
+            }
+        }
+        blame
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Config {
+        port: u32,
+        hosts: [&'static str; 2],
+    }
+
+    impl Diff for Config {
+        type Value = Config;
+
+        fn diff_with<'a, V: ::DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Config, visitor: &mut V) {
+            if visitor.enter_field(::PathSegment::Field("port")) {
+                self.port.diff_with(&other.port, visitor);
+                visitor.leave_field();
+            }
+            if visitor.enter_field(::PathSegment::Field("hosts")) {
+                self.hosts[..].diff_with(&other.hosts[..], visitor);
+                visitor.leave_field();
+            }
+        }
+    }
+
+    impl Patch for Config {
+        type Patch = Config;
+
+        fn patch(&self, other: &Config) -> Option<Config> {
+            if self.has_diff(other) {
+                return Some(other.clone());
+            }
+            None
+        }
+
+        fn apply(&mut self, patch: &Config) {
+            *self = patch.clone();
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new(Config { port: 80, hosts: ["a", "b"] });
+        let at = |secs| Utc.timestamp_opt(secs, 0).unwrap();
+        assert_eq!(history.record_at(Config { port: 81, hosts: ["a", "b"] }, at(10)), Some(1));
+        assert_eq!(history.record_at(Config { port: 81, hosts: ["a", "b"] }, at(20)), None);
+        assert_eq!(history.record_at(Config { port: 81, hosts: ["c", "d"] }, at(30)), Some(2));
+        assert_eq!(history.current(), &Config { port: 81, hosts: ["c", "d"] });
+        assert_eq!(history.version(), 2);
+        assert_eq!(history.revisions().count(), 2);
+        assert_eq!(history.at(0), Config { port: 80, hosts: ["a", "b"] });
+        assert_eq!(history.at(1), Config { port: 81, hosts: ["a", "b"] });
+        assert_eq!(history.at(2), *history.current());
+
+        assert_eq!(history.last_changed("port").unwrap().at, at(10));
+        assert_eq!(history.last_changed("hosts").unwrap().version, 2);
+        assert_eq!(history.last_changed("hosts.[1]").unwrap().version, 2);
+        assert_eq!(history.last_changed("").unwrap().version, 2);
+        assert!(history.last_changed("name").is_none());
+
+        let blame: Vec<_> = history.blame().into_iter().map(|(path, revision)| (path, revision.version)).collect();
+        assert_eq!(blame, vec![("hosts.[0]", 2), ("hosts.[1]", 2), ("port", 1)]);
+    }
+
+    #[test]
+    fn test_history_resumed() {
+        let mut history = History::new(Config { port: 80, hosts: ["a", "b"] });
+        history.record(Config { port: 81, hosts: ["a", "b"] });
+        history.record(Config { port: 81, hosts: ["c", "d"] });
+        let store = history.store().clone();
+        assert_eq!(store.revisions().next_back().unwrap().undo, Config { port: 81, hosts: ["a", "b"] });
+
+        let mut resumed = History::with_store(history.current().clone(), store);
+        assert_eq!(resumed.version(), 2);
+        assert_eq!(resumed.record(Config { port: 82, hosts: ["c", "d"] }), Some(3));
+        assert_eq!(resumed.at(0), Config { port: 80, hosts: ["a", "b"] });
+        assert_eq!(resumed.at(2), Config { port: 81, hosts: ["c", "d"] });
+    }
+
+    #[test]
+    fn test_touches() {
+        let revision = Revision {
+            version: 1,
+            at: Utc.timestamp_opt(0, 0).unwrap(),
+            changes: vec![OwnedDifference {
+                field: "owner.address".into(),
+                kind: ::ChangeKind::Modified,
+                left: String::new(),
+                right: String::new(),
+            }],
+            undo: (),
+        };
+        assert!(revision.touches("owner"));
+        assert!(revision.touches("owner.address"));
+        assert!(revision.touches("owner.address.city"));
+        assert!(!revision.touches("owner.addr"));
+        assert!(!revision.touches("name"));
+    }
+}
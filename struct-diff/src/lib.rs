@@ -1,43 +1,484 @@
 extern crate boolinator;
 extern crate chrono;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "serde_json")]
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
+#[cfg(feature = "toml")]
+extern crate toml;
 
-use boolinator::Boolinator;
 use chrono::DateTime;
 use std::borrow::Borrow;
 use std::cell::{Cell,RefCell};
-use std::fmt::Debug;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Write};
+use std::hash::Hash;
 use std::ops::Deref;
 use std::path::{Path,PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
 
+mod audit;
+mod compose;
+mod delta;
+#[cfg(any(feature = "serde_json", feature = "toml"))]
+mod document;
+mod field_set;
+mod history;
+mod options;
+mod patch;
+mod serde_diff;
+#[cfg(feature = "sql")]
+mod sql;
+mod summary;
+
+pub use audit::{AuditRecord, Entity};
+pub use compose::compose;
+pub use delta::{Change, Delta};
+pub use field_set::{FieldEnum, FieldSet, FieldSetIter};
+pub use history::{History, MemoryStore, Revision, RevisionStore};
+pub use options::DiffOptions;
+pub use patch::Patch;
+pub use serde_diff::{serde_diff, SerializeError};
+#[cfg(feature = "sql")]
+pub use sql::{update_set, update_sql};
+pub use summary::DiffSummary;
+
+/// Differences are reported in a deterministic order: fields in declaration
+/// order, sequence elements by index, keyed elements in the order of the left
+/// side followed by those only found on the right. Use `DiffOptions::sorted`
+/// or `path_order` to order them by path instead.
 pub trait Diff {
     type Value: Debug + PartialEq + ?Sized;
 
-    fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>>;
+    fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
+        let mut collector = Collector::new();
+        self.diff_with(other, &mut collector);
+        collector.into_diffs()
+    }
+
+    /// Streams differences into `visitor` instead of collecting them
+    fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V);
+
+    /// Returns `true` if `diff` would report any difference.
+    /// Stops at the first one and allocates nothing, except for
+    /// fields with `#[diff(key)]`, see `has_diff_keyed`.
+    fn has_diff(&self, other: &Self::Value) -> bool {
+        let mut found = FirstDifference(false);
+        self.diff_with(other, &mut found);
+        found.0
+    }
+
+    /// Formats `self` as reported in a difference when it changed as a whole:
+    /// like `Debug`, but derived impls show `#[diff(redact)]` fields as `<redacted>`
+    fn fmt_shown(&self, f: &mut fmt::Formatter) -> fmt::Result where Self: Debug {
+        Debug::fmt(self, f)
+    }
+
+    /// `self` formatted with `fmt_shown`, to be passed to `DiffVisitor::leaf`
+    fn shown(&self) -> &Shown<Self> {
+        Shown::new(self)
+    }
+}
+
+/// Value reported as a whole, see `Diff::fmt_shown`
+#[repr(transparent)]
+pub struct Shown<T: ?Sized>(T);
+
+impl<T: ?Sized> Shown<T> {
+    fn new(value: &T) -> &Shown<T> {
+        // Shown is a transparent wrapper, so it has the layout of T
+        unsafe { &*(value as *const T as *const Shown<T>) }
+    }
+}
+
+impl<T: Diff + Debug + ?Sized> Debug for Shown<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_shown(f)
+    }
+}
+
+/// Receives differences as `Diff::diff_with` walks two values
+pub trait DiffVisitor<'a> {
+    /// Called before descending into `segment`.
+    /// Returning `false` skips the subtree, `leave_field` is not called then.
+    fn enter_field(&mut self, segment: PathSegment<'a>) -> bool {
+        let _ = segment;
+        true
+    }
+
+    /// Called after the subtree entered by `enter_field` is done
+    fn leave_field(&mut self) {}
+
+    /// Called right after entering a field. Returning `true` makes the field
+    /// be reported as a single difference instead of being walked.
+    fn collapse(&self) -> bool {
+        false
+    }
+
+    /// Called for every difference found at the current path
+    fn leaf(&mut self, left: &'a Debug, right: &'a Debug, kind: ChangeKind);
+}
+
+/// Stops the walk at the first difference
+struct FirstDifference(bool);
+
+impl<'a> DiffVisitor<'a> for FirstDifference {
+    fn enter_field(&mut self, _segment: PathSegment<'a>) -> bool {
+        !self.0
+    }
+
+    fn leaf(&mut self, _left: &'a Debug, _right: &'a Debug, _kind: ChangeKind) {
+        self.0 = true;
+    }
+}
+
+/// Kind of the change between `left` and `right` when reported as a single
+/// difference, e.g. for `#[diff(redact)]` fields, `None` if they don't differ.
+/// A change below the root is `Modified`.
+pub fn change_kind<T: Diff + ?Sized>(left: &T, right: &T::Value) -> Option<ChangeKind> {
+    let mut first = FirstKind { depth: 0, kind: None };
+    left.diff_with(right, &mut first);
+    first.kind
+}
+
+/// Stops the walk at the first difference, keeping its kind
+struct FirstKind {
+    depth: usize,
+    kind: Option<ChangeKind>,
+}
+
+impl<'a> DiffVisitor<'a> for FirstKind {
+    fn enter_field(&mut self, _segment: PathSegment<'a>) -> bool {
+        if self.kind.is_some() {
+            return false;
+        }
+        self.depth += 1;
+        true
+    }
+
+    fn leave_field(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn leaf(&mut self, _left: &'a dyn Debug, _right: &'a dyn Debug, kind: ChangeKind) {
+        if self.kind.is_none() {
+            self.kind = Some(if self.depth == 0 { kind } else { ChangeKind::Modified });
+        }
+    }
+}
+
+/// Kind of change a `Difference` describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Value appeared, e.g. `None` became `Some`
+    Added,
+    /// Value disappeared, e.g. `Some` became `None`
+    Removed,
+    /// Value was changed in place
+    Modified,
+    /// Enum switched from one variant to another
+    VariantChanged { from: &'static str, to: &'static str },
+    /// First difference left out after `DiffOptions::max_differences` was reached
+    Truncated,
+}
+
+impl ChangeKind {
+    /// Lowercase name of the kind, e.g. for structured logs
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Modified => "modified",
+            ChangeKind::VariantChanged { .. } => "variant_changed",
+            ChangeKind::Truncated => "truncated",
+        }
+    }
+
+    /// Kind of the same change seen from the other side,
+    /// `Added` and `Removed` swapped and `VariantChanged` reversed
+    pub fn invert(self) -> ChangeKind {
+        match self {
+            ChangeKind::Added => ChangeKind::Removed,
+            ChangeKind::Removed => ChangeKind::Added,
+            ChangeKind::VariantChanged { from, to } => ChangeKind::VariantChanged { from: to, to: from },
+            kind => kind,
+        }
+    }
 }
 
 /// Field that differs
 #[derive(Debug)]
 pub struct Difference<'a> {
     pub field: String,
+    pub kind: ChangeKind,
     pub left: &'a Debug,
     pub right: &'a Debug,
 }
 
+/// Renders as `path: left -> right`
+impl<'a> fmt::Display for Difference<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        write!(f, "{:?} -> {:?}", self.left, self.right)
+    }
+}
+
+impl<'a> Difference<'a> {
+    /// Renders both sides, so that the difference can outlive the compared values
+    pub fn into_owned(self) -> OwnedDifference {
+        OwnedDifference {
+            left: format!("{:?}", self.left),
+            right: format!("{:?}", self.right),
+            field: self.field,
+            kind: self.kind,
+        }
+    }
+
+    /// Swaps both sides, i.e. the difference of right to left
+    pub fn invert(self) -> Difference<'a> {
+        Difference { field: self.field, kind: self.kind.invert(), left: self.right, right: self.left }
+    }
+}
+
+/// `Difference` with both sides rendered with `Debug`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnedDifference {
+    pub field: String,
+    pub kind: ChangeKind,
+    pub left: String,
+    pub right: String,
+}
+
+impl OwnedDifference {
+    /// Swaps both sides, e.g. to undo the change
+    pub fn invert(self) -> OwnedDifference {
+        OwnedDifference { field: self.field, kind: self.kind.invert(), left: self.right, right: self.left }
+    }
+}
+
+/// Renders as `path: left -> right`
+impl fmt::Display for OwnedDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        write!(f, "{} -> {}", self.left, self.right)
+    }
+}
+
+/// Stands in for both sides of a difference in a `#[diff(redact)]` field
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Redacted;
+
+impl Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Stands in for the absent side of an added or removed key or element
+struct Missing;
+
+impl Debug for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<missing>")
+    }
+}
+
+/// One step of the path from the compared value to a difference
+#[derive(Debug, Clone, Copy)]
+pub enum PathSegment<'a> {
+    /// Struct field, named or positional
+    Field(&'a str),
+    /// Sequence element
+    Index(usize),
+    /// Enum variant the following fields belong to
+    Variant(&'a str),
+    /// Sequence element matched by key, see `diff_keyed`. `occurrence` tells
+    /// elements sharing a key apart, it is 0 for the first one and rendered
+    /// from the second one on, as in `[id=1#2]`.
+    Key { name: &'a str, value: &'a Debug, occurrence: usize },
+    /// Element of an unordered sequence found on the left only,
+    /// by its index there, see `diff_unordered`
+    Removed(usize),
+    /// Element of an unordered sequence found on the right only, by its index there
+    Added(usize),
+}
+
+impl<'a> fmt::Display for PathSegment<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PathSegment::Field(name) | PathSegment::Variant(name) => f.write_str(name),
+            PathSegment::Index(i) => write!(f, "[{}]", i),
+            PathSegment::Key { name, value, occurrence: 0 } => write!(f, "[{}={:?}]", name, value),
+            PathSegment::Key { name, value, occurrence } => write!(f, "[{}={:?}#{}]", name, value, occurrence + 1),
+            PathSegment::Removed(i) => write!(f, "[-{}]", i),
+            PathSegment::Added(i) => write!(f, "[+{}]", i),
+        }
+    }
+}
+
+/// Keys are compared by their `Debug` rendering
+impl<'a> PartialEq for PathSegment<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (*self, *other) {
+            (PathSegment::Field(a), PathSegment::Field(b)) => a == b,
+            (PathSegment::Index(a), PathSegment::Index(b)) => a == b,
+            (PathSegment::Variant(a), PathSegment::Variant(b)) => a == b,
+            (PathSegment::Removed(a), PathSegment::Removed(b)) => a == b,
+            (PathSegment::Added(a), PathSegment::Added(b)) => a == b,
+            (PathSegment::Key { name: a, value: x, occurrence: i },
+             PathSegment::Key { name: b, value: y, occurrence: j }) => {
+                a == b && i == j && format!("{:?}", x) == format!("{:?}", y)
+            },
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Eq for PathSegment<'a> {}
+
+/// Orders rendered paths, as found in `Difference::field`, segment by segment.
+///
+/// Indices, including those of unordered elements, and numeric key values
+/// compare as numbers, so `[9]` comes
+/// before `[10]`, everything else compares as strings. A path comes
+/// before the paths below it.
+pub fn path_order(a: &str, b: &str) -> Ordering {
+    let keys = |path| split_path(path).into_iter().map(SegmentKey::parse);
+    keys(a).cmp(keys(b))
+}
+
+/// Splits a rendered path on dots outside of brackets
+fn split_path(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    if path.is_empty() {
+        return segments;
+    }
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in path.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            '.' if depth == 0 => {
+                segments.push(&path[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    segments.push(&path[start..]);
+    segments
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SegmentKey<'s> {
+    Name(&'s str),
+    Index(usize),
+    /// `[-i]` or `[+i]`
+    Unordered(&'s str, usize),
+    /// `[name=value]` or `[name=value#occurrence]`
+    Key(&'s str, Result<i64, &'s str>, usize),
+}
+
+impl<'s> SegmentKey<'s> {
+    fn parse(segment: &'s str) -> Self {
+        if !(segment.starts_with('[') && segment.ends_with(']')) {
+            return SegmentKey::Name(segment);
+        }
+        let inner = &segment[1..segment.len() - 1];
+        if inner.starts_with('-') || inner.starts_with('+') {
+            if let Ok(i) = inner[1..].parse() {
+                return SegmentKey::Unordered(&inner[..1], i);
+            }
+        } else if let Ok(i) = inner.parse() {
+            return SegmentKey::Index(i);
+        }
+        match inner.find('=') {
+            Some(eq) => {
+                let mut value = &inner[eq + 1..];
+                let mut occurrence = 1;
+                if let Some(hash) = value.rfind('#') {
+                    if let Ok(n) = value[hash + 1..].parse() {
+                        occurrence = n;
+                        value = &value[..hash];
+                    }
+                }
+                SegmentKey::Key(&inner[..eq], value.parse().map_err(|_| value), occurrence)
+            },
+            None => SegmentKey::Name(segment),
+        }
+    }
+}
+
+/// Visitor behind `Diff::diff`, accumulates differences.
+///
+/// The current path is kept as a stack of segments and rendered
+/// only once, when a difference is found.
+#[derive(Default)]
+pub struct Collector<'a> {
+    path: Vec<PathSegment<'a>>,
+    diffs: Vec<Difference<'a>>,
+}
+
+impl<'a> Collector<'a> {
+    pub fn new() -> Self {
+        Collector {
+            path: Vec::new(),
+            diffs: Vec::new(),
+        }
+    }
+
+    pub fn into_diffs(self) -> Option<Vec<Difference<'a>>> {
+        if self.diffs.is_empty() {
+            return None;
+        }
+        Some(self.diffs)
+    }
+}
+
+impl<'a> DiffVisitor<'a> for Collector<'a> {
+    fn enter_field(&mut self, segment: PathSegment<'a>) -> bool {
+        self.path.push(segment);
+        true
+    }
+
+    fn leave_field(&mut self) {
+        self.path.pop();
+    }
+
+    fn leaf(&mut self, left: &'a Debug, right: &'a Debug, kind: ChangeKind) {
+        let mut field = String::new();
+        for (i, segment) in self.path.iter().enumerate() {
+            if i > 0 {
+                field.push('.');
+            }
+            let _ = write!(field, "{}", segment);
+        }
+        self.diffs.push(Difference { field, kind, left, right });
+    }
+}
+
 macro_rules! impl_for_prim {
     ($t: ty) => {
         impl Diff for $t {
             type Value = $t;
 
-            fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
-                self.ne(other).as_some_from(||
-                    vec![Difference {
-                        field: String::new(),
-                        left: self,
-                        right: other,
-                    }]
-                )
+            fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+                if self.ne(other) {
+                    visitor.leaf(self, other, ChangeKind::Modified);
+                }
+            }
+
+            fn has_diff(&self, other: &Self::Value) -> bool {
+                self.ne(other)
             }
         }       
     };
@@ -47,14 +488,14 @@ macro_rules! impl_for_prim_ref {
     ($t: ty) => {
         impl<'b> Diff for &'b $t {
             type Value = &'b $t;
-            fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
-                self.ne(other).as_some_from(||
-                    vec![Difference {
-                        field: String::new(),
-                        left: self,
-                        right: other,
-                    }]
-                )
+            fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+                if self.ne(other) {
+                    visitor.leaf(self, other, ChangeKind::Modified);
+                }
+            }
+
+            fn has_diff(&self, other: &Self::Value) -> bool {
+                self.ne(other)
             }
         }
     };
@@ -64,14 +505,18 @@ macro_rules! impl_for_wrapper {
     ($t: tt) => {
         impl<T> Diff for $t<T> where $t<T>: Borrow<T>, T: Debug + Diff<Value=T> + PartialEq {
             type Value = T;
-            fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
-                self.borrow().ne(other.borrow()).as_some_from(||
-                    vec![Difference {
-                        field: String::new(),
-                        left: self.borrow(),
-                        right: other.borrow(),
-                    }]
-                )
+            fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+                if self.borrow().ne(other.borrow()) {
+                    visitor.leaf(self.borrow().shown(), other.shown(), ChangeKind::Modified);
+                }
+            }
+
+            fn has_diff(&self, other: &Self::Value) -> bool {
+                self.borrow().ne(other.borrow())
+            }
+
+            fn fmt_shown(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.borrow().fmt_shown(f)
             }
         }
     };
@@ -84,6 +529,7 @@ impl<B Diff for B where B: Borrow<T>, T: Debug + Diff<Value=T> + PartialEq {
         if self.borrow() != other.borrow() {
             return Some(vec![Difference {
                 field: String::new(),
+                kind: ChangeKind::Modified,
                 left: self.borrow(),
                 right: other.borrow(),
             }]);
@@ -95,102 +541,265 @@ impl<B Diff for B where B: Borrow<T>, T: Debug + Diff<Value=T> + PartialEq {
 
 impl<T> Diff for Cell<T> where T: Copy + Debug + Diff<Value=T> + PartialEq {
     type Value = T;
-    fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
-        self.get().ne(other).as_some_from(||
-            vec![Difference {
-                field: String::new(),
-                left: self,
-                right: other,
-            }]
-        )
+    fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+        if self.get().ne(other) {
+            visitor.leaf(self.shown(), other.shown(), ChangeKind::Modified);
+        }
+    }
+
+    fn has_diff(&self, other: &Self::Value) -> bool {
+        self.get().ne(other)
+    }
+
+    fn fmt_shown(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cell").field("value", self.get().shown()).finish()
     }
 }
 
 impl<T: chrono::TimeZone> Diff for DateTime<T> {
     type Value = DateTime<T>;
-    fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
-        self.ne(other).as_some_from(||
-            vec![Difference {
-                field: String::new(),
-                left: self,
-                right: other,
-            }]
-        )
+    fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+        if self.ne(other) {
+            visitor.leaf(self, other, ChangeKind::Modified);
+        }
+    }
+
+    fn has_diff(&self, other: &Self::Value) -> bool {
+        self.ne(other)
     }
 }
 
 impl<T> Diff for Option<T> where T: Debug + PartialEq + Diff<Value=T> {
     type Value = Option<T>;
-    fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
+    fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
         match (self, other) {
             (&Some(ref left), &Some(ref right)) => {
-                left.diff(right)
+                left.diff_with(right, visitor)
             }
             (&None, &Some(_)) => {
-                Some(vec![Difference { field: "none".into(), left: self, right: other }])
+                visitor.leaf(self.shown(), other.shown(), ChangeKind::Added)
             },
             (&Some(_), &None) => {
-                Some(vec![Difference { field: "some".into(), left: self, right: other }])
+                visitor.leaf(self.shown(), other.shown(), ChangeKind::Removed)
             },
-            (&None, &None) => None,
+            (&None, &None) => {},
+        }
+    }
+
+    fn has_diff(&self, other: &Self::Value) -> bool {
+        match (self, other) {
+            (&Some(ref left), &Some(ref right)) => left.has_diff(right),
+            (&None, &None) => false,
+            _ => true,
+        }
+    }
+
+    fn fmt_shown(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Some(ref value) => f.debug_tuple("Some").field(value.shown()).finish(),
+            None => f.write_str("None"),
         }
     }
 }
 
 impl<T> Diff for RefCell<T> where T: Debug + Diff<Value=T> + PartialEq {
     type Value = T;
-    fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
-        self.borrow().deref().ne(other).as_some_from(||
-            vec![Difference {
-                field: String::new(),
-                left: self,
-                right: other,
-            }]
-        )
+    fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+        if self.borrow().deref().ne(other) {
+            visitor.leaf(self.shown(), other.shown(), ChangeKind::Modified);
+        }
+    }
+
+    fn has_diff(&self, other: &Self::Value) -> bool {
+        self.borrow().deref().ne(other)
+    }
+
+    fn fmt_shown(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = self.borrow();
+        f.debug_struct("RefCell").field("value", value.deref().shown()).finish()
+    }
+}
+
+/// Diffs two entries of the same map, object or sequence under `segment`,
+/// an entry present on one side only is added or removed
+fn diff_entry<'a, T, V>(segment: PathSegment<'a>, left: Option<&'a T>, right: Option<&'a T>, visitor: &mut V)
+    where T: Diff<Value=T> + Debug + PartialEq, V: DiffVisitor<'a> + ?Sized
+{
+    let (left, right) = match (left, right) {
+        (Some(left), Some(right)) => (left, right),
+        (Some(left), None) => {
+            if visitor.enter_field(segment) {
+                visitor.leaf(left.shown(), &Missing, ChangeKind::Removed);
+                visitor.leave_field();
+            }
+            return;
+        },
+        (None, Some(right)) => {
+            if visitor.enter_field(segment) {
+                visitor.leaf(&Missing, right.shown(), ChangeKind::Added);
+                visitor.leave_field();
+            }
+            return;
+        },
+        (None, None) => return,
+    };
+    if visitor.enter_field(segment) {
+        if visitor.collapse() {
+            if left.has_diff(right) {
+                visitor.leaf(left.shown(), right.shown(), ChangeKind::Modified);
+            }
+        } else {
+            left.diff_with(right, visitor);
+        }
+        visitor.leave_field();
     }
 }
 
+/// Diffs two sequences matching elements by `key` instead of by position,
+/// as generated for fields with `#[diff(key = "name")]`.
+///
+/// Matched elements are walked under `[name=key]`, elements with a key found
+/// on one side only are reported as added or removed. Order is ignored, except
+/// that elements sharing a key are matched in the order they appear on each side,
+/// under `[name=key#n]` from the second one on.
+pub fn diff_keyed<'a, T, K, F, V>(left: &'a [T], right: &'a [T], name: &'a str, key: F, visitor: &mut V)
+    where T: Diff<Value=T> + Debug + PartialEq,
+          K: Debug + Hash + Eq + 'a,
+          F: Fn(&'a T) -> &'a K,
+          V: DiffVisitor<'a> + ?Sized
+{
+    for (value, occurrence, left, right) in match_keyed(left, right, key) {
+        diff_entry(PathSegment::Key { name, value, occurrence }, left, right, visitor);
+    }
+}
+
+/// Same as `Diff::has_diff` for sequences diffed with `diff_keyed`.
+/// Unlike other `has_diff` impls, this allocates to index elements by key.
+pub fn has_diff_keyed<'a, T, K, F>(left: &'a [T], right: &'a [T], key: F) -> bool
+    where T: Diff<Value=T> + Debug + PartialEq,
+          K: Hash + Eq + 'a,
+          F: Fn(&'a T) -> &'a K
+{
+    if left.len() != right.len() {
+        return true;
+    }
+    match_keyed(left, right, key).into_iter().any(|(_, _, left, right)| match (left, right) {
+        (Some(left), Some(right)) => left.has_diff(right),
+        _ => true,
+    })
+}
+
+/// Pairs elements of `left` and `right` by key, along with their occurrence
+/// of that key: those of `left` in order, then those of `right` left without
+/// a match. The n-th element with a given key on one side is matched with
+/// the n-th one with that key on the other.
+fn match_keyed<'a, T, K, F>(left: &'a [T], right: &'a [T], key: F) -> Vec<(&'a K, usize, Option<&'a T>, Option<&'a T>)>
+    where K: Hash + Eq + 'a,
+          F: Fn(&'a T) -> &'a K
+{
+    let mut right_keyed: HashMap<&K, Vec<&T>> = HashMap::new();
+    for item in right {
+        right_keyed.entry(key(item)).or_default().push(item);
+    }
+    let mut left_seen: HashMap<&K, usize> = HashMap::new();
+    let mut pairs = Vec::with_capacity(left.len().max(right.len()));
+    for item in left {
+        let value = key(item);
+        let occurrence = left_seen.entry(value).or_insert(0);
+        let other = right_keyed.get(value).and_then(|items| items.get(*occurrence)).cloned();
+        pairs.push((value, *occurrence, Some(item), other));
+        *occurrence += 1;
+    }
+    let mut right_seen: HashMap<&K, usize> = HashMap::new();
+    for item in right {
+        let value = key(item);
+        let occurrence = right_seen.entry(value).or_insert(0);
+        if *occurrence >= left_seen.get(value).cloned().unwrap_or(0) {
+            pairs.push((value, *occurrence, None, Some(item)));
+        }
+        *occurrence += 1;
+    }
+    pairs
+}
+
+/// Diffs two sequences as multisets, as generated for fields with `#[diff(unordered)]`.
+///
+/// Elements are matched by equality regardless of position. Those left
+/// without a match on the other side are reported as removed under `[-i]`
+/// or added under `[+i]`, `i` being their index on their own side.
+pub fn diff_unordered<'a, T, V>(left: &'a [T], right: &'a [T], visitor: &mut V)
+    where T: Diff<Value=T> + Debug + PartialEq, V: DiffVisitor<'a> + ?Sized
+{
+    let mut matched = vec![false; right.len()];
+    for (i, item) in left.iter().enumerate() {
+        match (0..right.len()).find(|&j| !matched[j] && right[j] == *item) {
+            Some(j) => matched[j] = true,
+            None => diff_entry(PathSegment::Removed(i), Some(item), None, visitor),
+        }
+    }
+    for (j, item) in right.iter().enumerate().filter(|&(j, _)| !matched[j]) {
+        diff_entry(PathSegment::Added(j), None, Some(item), visitor);
+    }
+}
+
+/// Same as `Diff::has_diff` for sequences diffed with `diff_unordered`.
+/// Compares how often each element occurs on both sides, without allocating.
+pub fn has_diff_unordered<T: PartialEq>(left: &[T], right: &[T]) -> bool {
+    if left.len() != right.len() {
+        return true;
+    }
+    let count = |items: &[T], item: &T| items.iter().filter(|other| *other == item).count();
+    left.iter().enumerate().any(|(i, item)| !left[..i].contains(item) && count(left, item) != count(right, item))
+}
+
+/// Elements are compared by index, those past the end of
+/// the shorter side are reported as added or removed
 impl<T> Diff for [T] where T: Debug + Diff<Value=T> + PartialEq {
     type Value = [T];
-    fn diff<'a>(&'a self, other: &'a Self::Value) -> Option<Vec<Difference<'a>>> {
-        self.ne(other).and_option_from(|| {
-            let diffs: Vec<_> = self
-                .into_iter()
-                .zip(other.iter())
-                .enumerate()
-                .filter_map(|(i, (left, right))| {
-                    left
-                        .diff(right)
-                        .map(|inner_diffs|
-                            inner_diffs.into_iter().map(move |diff| 
-                                Difference {
-                                    field: {
-                                      // Use push for speed
-                                      // guesstimate string size
-                                      let mut s=String::with_capacity(3+10+diff.field.len());
-                                      s.push_str("[");
-                                      s.push_str(&i.to_string());
-                                      if !diff.field.is_empty() {
-                                        s.push_str("].");
-                                        s.push_str(&diff.field);
-                                      } else {
-                                        s.push_str("]");
-                                      }
-                                      s
-                                    },
-                                    left: diff.left,
-                                    right: diff.right,
-                                }
-                            )
-                        )
-                })
-                .flat_map(|x|x)
-                .collect();
-            (!diffs.is_empty()).as_some(diffs)
-        })
+    fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+        for i in 0..self.len().max(other.len()) {
+            diff_entry(PathSegment::Index(i), self.get(i), other.get(i), visitor);
+        }
+    }
+
+    fn has_diff(&self, other: &Self::Value) -> bool {
+        self.len() != other.len() || self.iter().zip(other.iter()).any(|(left, right)| left.has_diff(right))
+    }
+
+    fn fmt_shown(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter().map(Diff::shown)).finish()
     }
 }
 
+macro_rules! impl_for_sequence {
+    ($t: ty, $($params: tt)*) => {
+        impl<$($params)*> Diff for $t where T: Debug + Diff<Value=T> + PartialEq {
+            type Value = $t;
+            fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+                self[..].diff_with(&other[..], visitor)
+            }
+
+            fn has_diff(&self, other: &Self::Value) -> bool {
+                self[..].has_diff(&other[..])
+            }
+
+            fn fmt_shown(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self[..].fmt_shown(f)
+            }
+        }
+    };
+}
+
+impl_for_sequence!(Vec<T>, T);
+
+macro_rules! impl_for_array {
+    ($($n: expr),*) => {
+        $(impl_for_sequence!([T; $n], T);)*
+    };
+}
+
+impl_for_array!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+                17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32);
 impl_for_wrapper!(Arc);
 impl_for_wrapper!(Box);
 impl_for_wrapper!(Rc);
@@ -211,12 +820,14 @@ impl_for_prim!(char);
 impl_for_prim!(String);
 impl_for_prim!(chrono::NaiveDateTime);
 impl_for_prim!(PathBuf);
+impl_for_prim!(Redacted);
 impl_for_prim_ref!(Path);
 impl_for_prim_ref!(str);
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use boolinator::Boolinator;
 
     macro_rules! test_diff_wrappers {
         ( $a: expr, $b: expr, $f:ident $(, $w:tt )* ) => {
@@ -231,7 +842,7 @@ mod test {
 
     impl<'a> PartialEq for Difference<'a> {
         fn eq(&self, other: &Self) -> bool {
-            self.field.eq(&other.field) && format!("{:?}", self.left) == format!("{:?}", other.left) && format!("{:?}", self.right) == format!("{:?}", other.right)
+            self.field.eq(&other.field) && self.kind == other.kind && format!("{:?}", self.left) == format!("{:?}", other.left) && format!("{:?}", self.right) == format!("{:?}", other.right)
         }
     }
 
@@ -240,8 +851,9 @@ mod test {
               T1: Diff<Value=T> + Borrow<T>,
               T2: Diff<Value=T> + Borrow<T> {
         let r = i_a.diff(i_b.borrow());
+        assert_eq!(i_a.has_diff(i_b.borrow()), r.is_some());
         if i_a.borrow() != i_b.borrow() {
-            assert_eq!(r, Some(vec![Difference { field: String::new(), left: i_a.borrow(), right: i_b.borrow() }]));
+            assert_eq!(r, Some(vec![Difference { field: String::new(), kind: ChangeKind::Modified, left: i_a.borrow(), right: i_b.borrow() }]));
         } else {
             assert_eq!(r, None);
         }
@@ -250,9 +862,10 @@ mod test {
     fn test_diff_array<T>(i_a: &[T], i_b: &[T]) 
         where T: Debug + Diff<Value=T> + PartialEq {
         let r = i_a.diff(i_b);
+        assert_eq!(i_a.has_diff(i_b), r.is_some());
         let truth: Vec<_> = i_a.iter().zip(i_b).enumerate().filter_map(|(i, (a,b))| {
             if a != b {
-                Some(vec![Difference { field: format!("[{}]", i), left: a, right: b}])
+                Some(vec![Difference { field: format!("[{}]", i), kind: ChangeKind::Modified, left: a, right: b}])
             } else {
                 None
             }
@@ -387,4 +1000,155 @@ mod test {
         test_diff(PathBuf::from("/a"), PathBuf::from("/a"));
         test_diff(PathBuf::from("/a"), PathBuf::from("/b"));
     }
+
+    #[test]
+    fn test_collector_path() {
+        let (a, b) = ([Some(1), Some(2)], [Some(1), None]);
+        let mut collector = Collector::new();
+        collector.enter_field(PathSegment::Field("a"));
+        a.diff_with(&b, &mut collector);
+        collector.leave_field();
+        5.diff_with(&6, &mut collector);
+        assert_eq!(collector.into_diffs(), Some(vec![
+            Difference { field: "a.[1]".into(), kind: ChangeKind::Removed, left: &Some(2), right: &None::<i32> },
+            Difference { field: String::new(), kind: ChangeKind::Modified, left: &5, right: &6 },
+        ]));
+    }
+
+    #[test]
+    fn test_visitor() {
+        struct Paths(Vec<String>, usize);
+
+        impl<'a> DiffVisitor<'a> for Paths {
+            fn enter_field(&mut self, segment: PathSegment<'a>) -> bool {
+                self.0.push(segment.to_string());
+                segment != PathSegment::Index(0)
+            }
+
+            fn leaf(&mut self, _left: &'a Debug, _right: &'a Debug, _kind: ChangeKind) {
+                self.1 += 1;
+            }
+        }
+
+        let mut paths = Paths(Vec::new(), 0);
+        [1, 2, 3].diff_with(&[4, 5, 3], &mut paths);
+        assert_eq!(paths.0, vec!["[0]", "[1]", "[2]"]);
+        assert_eq!(paths.1, 1);
+    }
+
+    #[test]
+    fn test_display() {
+        let d = Difference { field: "a.[0]".into(), kind: ChangeKind::Modified, left: &1, right: &"b" };
+        assert_eq!(d.to_string(), "a.[0]: 1 -> \"b\"");
+        let d = Difference { field: String::new(), kind: ChangeKind::Modified, left: &1, right: &2 };
+        assert_eq!(d.to_string(), "1 -> 2");
+    }
+
+    #[test]
+    fn test_option() {
+        let none: Option<u32> = None;
+        assert_eq!(Some(1).diff(&Some(1)), None);
+        assert!(!none.has_diff(&None));
+        assert!(Some(1).has_diff(&Some(2)));
+        assert!(none.has_diff(&Some(2)));
+        assert_eq!(Some(1).diff(&Some(2)), Some(vec![Difference { field: String::new(), kind: ChangeKind::Modified, left: &1, right: &2 }]));
+        assert_eq!(none.diff(&Some(2)), Some(vec![Difference { field: String::new(), kind: ChangeKind::Added, left: &none, right: &Some(2) }]));
+        assert_eq!(Some(1).diff(&none), Some(vec![Difference { field: String::new(), kind: ChangeKind::Removed, left: &Some(1), right: &none }]));
+    }
+
+    #[test]
+    fn test_path_order() {
+        let mut paths = vec!["b", "a.[10]", "", "a.[9].x", "a", "a.[id=\"x.y\"]", "a.[id=10]", "a.[id=9#10]", "a.[id=9]",
+                             "a.[9]", "a.[+10]", "a.[-2]", "a.[+9]", "a.[id=9#2]"];
+        paths.sort_by(|a, b| path_order(a, b));
+        assert_eq!(paths, vec![
+            "", "a", "a.[9]", "a.[9].x", "a.[10]", "a.[+9]", "a.[+10]", "a.[-2]",
+            "a.[id=9]", "a.[id=9#2]", "a.[id=9#10]", "a.[id=10]", "a.[id=\"x.y\"]", "b",
+        ]);
+        assert_eq!(split_path("a.[k=\"x.y\"].b"), vec!["a", "[k=\"x.y\"]", "b"]);
+    }
+
+    #[test]
+    fn test_slice_length() {
+        let (short, long) = (vec![1], vec![1, 2, 3]);
+        assert!(short.has_diff(&long));
+        assert!(long.has_diff(&short));
+        let rendered: Vec<_> = short.diff(&long).unwrap().iter().map(|d| (d.to_string(), d.kind)).collect();
+        assert_eq!(rendered, vec![
+            ("[1]: <missing> -> 2".to_owned(), ChangeKind::Added),
+            ("[2]: <missing> -> 3".to_owned(), ChangeKind::Added),
+        ]);
+        let rendered: Vec<_> = long.diff(&short).unwrap().iter().map(|d| (d.to_string(), d.kind)).collect();
+        assert_eq!(rendered, vec![
+            ("[1]: 2 -> <missing>".to_owned(), ChangeKind::Removed),
+            ("[2]: 3 -> <missing>".to_owned(), ChangeKind::Removed),
+        ]);
+        assert!(!Vec::<u32>::new().has_diff(&Vec::new()));
+    }
+
+    #[test]
+    fn test_invert() {
+        let none: Option<u32> = None;
+        let inverted: Vec<_> = Some(1).diff(&none).unwrap().into_iter().map(Difference::invert).collect();
+        assert_eq!(Some(inverted), none.diff(&Some(1)));
+        let d = Difference { field: "a".into(), kind: ChangeKind::VariantChanged { from: "A", to: "B" }, left: &1, right: &2 };
+        let owned = d.into_owned().invert();
+        assert_eq!(owned.to_string(), "a: 2 -> 1");
+        assert_eq!(owned.kind, ChangeKind::VariantChanged { from: "B", to: "A" });
+        assert_eq!(owned.clone().invert().invert(), owned);
+        assert_eq!(ChangeKind::Modified.invert(), ChangeKind::Modified);
+    }
+
+    #[test]
+    fn test_diff_keyed() {
+        let a: Vec<String> = vec!["a1".into(), "b2".into(), "c3".into()];
+        let b: Vec<String> = vec!["c3".into(), "d4".into(), "a9".into()];
+        let mut collector = Collector::new();
+        diff_keyed(&a, &b, "initial", |item| &item.as_bytes()[0], &mut collector);
+        let rendered: Vec<_> = collector.into_diffs().unwrap().iter().map(|d| d.to_string()).collect();
+        assert_eq!(rendered, vec![
+            "[initial=97]: \"a1\" -> \"a9\"",
+            "[initial=98]: \"b2\" -> <missing>",
+            "[initial=100]: <missing> -> \"d4\"",
+        ]);
+        assert!(has_diff_keyed(&a, &b, |item| &item.as_bytes()[0]));
+        let shuffled = vec![a[2].clone(), a[0].clone(), a[1].clone()];
+        assert!(!has_diff_keyed(&a, &shuffled, |item| &item.as_bytes()[0]));
+        assert_eq!(PathSegment::Key { name: "id", value: &1, occurrence: 0 },
+                   PathSegment::Key { name: "id", value: &1u8, occurrence: 0 });
+        assert!(PathSegment::Key { name: "id", value: &1, occurrence: 0 } != PathSegment::Key { name: "id", value: &1, occurrence: 1 });
+    }
+
+    #[test]
+    fn test_diff_keyed_duplicates() {
+        let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect::<Vec<_>>();
+        let (a, b, c) = (strings(&["a0", "a0"]), strings(&["a0", "b0"]), strings(&["a0", "a1", "a2"]));
+        assert!(has_diff_keyed(&a, &b, |item| &item.as_bytes()[0]));
+        assert!(has_diff_keyed(&b, &a, |item| &item.as_bytes()[0]));
+        assert!(!has_diff_keyed(&a, &a, |item| &item.as_bytes()[0]));
+        let fields = |left: &[String], right: &[String]| {
+            let mut collector = Collector::new();
+            diff_keyed(left, right, "id", |item| &item.as_bytes()[0], &mut collector);
+            collector.into_diffs().unwrap_or_default().iter().map(|d| d.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(fields(&a, &b), vec!["[id=97#2]: \"a0\" -> <missing>", "[id=98]: <missing> -> \"b0\""]);
+        assert_eq!(fields(&a[..1], &c), vec!["[id=97#2]: <missing> -> \"a1\"", "[id=97#3]: <missing> -> \"a2\""]);
+        assert_eq!(fields(&c[1..], &c[..2]), vec!["[id=97]: \"a1\" -> \"a0\"", "[id=97#2]: \"a2\" -> \"a1\""]);
+    }
+
+    #[test]
+    fn test_diff_unordered() {
+        let a = ["x", "y", "y", "z"];
+        let b = ["y", "w", "x", "y"];
+        let mut collector = Collector::new();
+        diff_unordered(&a, &b, &mut collector);
+        let rendered: Vec<_> = collector.into_diffs().unwrap().iter().map(|d| (d.to_string(), d.kind)).collect();
+        assert_eq!(rendered, vec![
+            ("[-3]: \"z\" -> <missing>".to_owned(), ChangeKind::Removed),
+            ("[+1]: <missing> -> \"w\"".to_owned(), ChangeKind::Added),
+        ]);
+        assert!(has_diff_unordered(&a, &b));
+        assert!(!has_diff_unordered(&a, &["z", "y", "x", "y"]));
+        assert!(has_diff_unordered(&a, &["z", "x", "x", "y"]));
+    }
 }
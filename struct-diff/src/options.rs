@@ -0,0 +1,356 @@
+use std::fmt::Debug;
+
+use {path_order, ChangeKind, Collector, Diff, DiffVisitor, Difference, PathSegment};
+
+/// Settings for a single diff call, so that callers of the same type
+/// can each look at a different part of it.
+///
+/// Path patterns are matched segment by segment against the paths
+/// reported in `Difference::field`:
+///
+/// * `**` matches any number of segments, including none
+/// * `*` matches exactly one segment
+/// * `[*]` matches one sequence index, key or unordered element
+/// * anything else matches a field, variant, index (`[3]`), key (`[id=42]`)
+///   or unordered element (`[-2]`, `[+1]`) literally
+///
+/// ```
+/// # use struct_diff::DiffOptions;
+/// let options = DiffOptions::new()
+///     .exclude("**.updated_at")
+///     .exclude("items.[*].cache")
+///     .max_depth(4)
+///     .max_differences(100);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    max_depth: Option<usize>,
+    max_differences: Option<usize>,
+    sorted: bool,
+}
+
+impl DiffOptions {
+    pub fn new() -> Self {
+        DiffOptions::default()
+    }
+
+    /// Only report differences at or below paths matching `pattern`.
+    /// Can be given several times, a difference needs to match any of them.
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.include.push(Pattern::parse(pattern));
+        self
+    }
+
+    /// Skip paths matching `pattern` together with everything below them.
+    /// Excluded subtrees are not visited at all.
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude.push(Pattern::parse(pattern));
+        self
+    }
+
+    /// Don't walk deeper than `depth` path segments: fields at that depth
+    /// are reported as a single `ChangeKind::Modified` difference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` is 0.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        assert!(depth > 0, "max_depth must be at least 1");
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Stop after `count` differences. If there are more, one extra
+    /// difference with `ChangeKind::Truncated` marks the cut.
+    pub fn max_differences(mut self, count: usize) -> Self {
+        self.max_differences = Some(count);
+        self
+    }
+
+    /// Sort differences returned by `diff` by path, see `path_order`.
+    /// Which differences `max_differences` keeps still follows the walk order.
+    /// Has no effect on `diff_with`, which streams differences as they are found.
+    pub fn sorted(mut self) -> Self {
+        self.sorted = true;
+        self
+    }
+
+    /// Same as `Diff::diff`, honoring these options
+    pub fn diff<'a, T>(&self, left: &'a T, right: &'a T::Value) -> Option<Vec<Difference<'a>>>
+        where T: Diff + ?Sized
+    {
+        let mut collector = Collector::new();
+        self.diff_with(left, right, &mut collector);
+        let mut diffs = collector.into_diffs();
+        if self.sorted {
+            if let Some(ref mut diffs) = diffs {
+                diffs.sort_by(|a, b| path_order(&a.field, &b.field));
+            }
+        }
+        diffs
+    }
+
+    /// Same as `Diff::diff_with`, honoring these options
+    pub fn diff_with<'a, T, V>(&self, left: &'a T, right: &'a T::Value, visitor: &mut V)
+        where T: Diff + ?Sized, V: DiffVisitor<'a> + ?Sized
+    {
+        let mut filter = Filter {
+            options: self,
+            inner: visitor,
+            path: Vec::new(),
+            included: Vec::new(),
+            count: 0,
+        };
+        left.diff_with(right, &mut filter);
+    }
+
+    fn is_excluded(&self, path: &[PathSegment]) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches(path))
+    }
+
+    fn is_included(&self, path: &[PathSegment]) -> bool {
+        self.include.is_empty() || self.include.iter().any(|pattern| pattern.matches(path))
+    }
+
+    /// Whether something below `path` could still be included
+    fn may_include_below(&self, path: &[PathSegment]) -> bool {
+        self.include.iter().any(|pattern| pattern.matches_prefix(path))
+    }
+}
+
+/// Applies `DiffOptions` on top of another visitor
+struct Filter<'o, 'a, 'v, V: DiffVisitor<'a> + ?Sized + 'v> {
+    options: &'o DiffOptions,
+    inner: &'v mut V,
+    path: Vec<PathSegment<'a>>,
+    /// For every entered segment, whether it is inside an included subtree
+    included: Vec<bool>,
+    /// Differences passed on so far, the truncation marker included
+    count: usize,
+}
+
+impl<'o, 'a, 'v, V: DiffVisitor<'a> + ?Sized + 'v> Filter<'o, 'a, 'v, V> {
+    /// Whether the current path lies in an included subtree
+    fn is_included(&self) -> bool {
+        match self.included.last() {
+            Some(&included) => included,
+            None => self.options.is_included(&self.path),
+        }
+    }
+
+    fn is_truncated(&self) -> bool {
+        match self.options.max_differences {
+            Some(max) => self.count > max,
+            None => false,
+        }
+    }
+}
+
+impl<'o, 'a, 'v, V: DiffVisitor<'a> + ?Sized + 'v> DiffVisitor<'a> for Filter<'o, 'a, 'v, V> {
+    fn enter_field(&mut self, segment: PathSegment<'a>) -> bool {
+        if self.is_truncated() {
+            return false;
+        }
+        let parent_included = self.is_included();
+        self.path.push(segment);
+        let included = parent_included || self.options.is_included(&self.path);
+        let visit = !self.options.is_excluded(&self.path)
+            && (included || self.options.may_include_below(&self.path))
+            && self.inner.enter_field(segment);
+        if !visit {
+            self.path.pop();
+            return false;
+        }
+        self.included.push(included);
+        true
+    }
+
+    fn leave_field(&mut self) {
+        self.inner.leave_field();
+        self.included.pop();
+        self.path.pop();
+    }
+
+    fn collapse(&self) -> bool {
+        match self.options.max_depth {
+            Some(depth) if self.path.len() >= depth => true,
+            _ => self.inner.collapse(),
+        }
+    }
+
+    fn leaf(&mut self, left: &'a Debug, right: &'a Debug, kind: ChangeKind) {
+        // differences at the root aren't under any field checked by enter_field
+        if !self.is_included() || self.is_truncated() || self.options.is_excluded(&self.path) {
+            return;
+        }
+        self.count += 1;
+        if self.is_truncated() {
+            self.inner.leaf(left, right, ChangeKind::Truncated);
+        } else {
+            self.inner.leaf(left, right, kind);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Glob {
+    /// `**`
+    AnyDepth,
+    /// `*`
+    Any,
+    /// `[*]`
+    AnyIndex,
+    Index(usize),
+    Name(String),
+}
+
+impl Glob {
+    fn parse(s: &str) -> Self {
+        match s {
+            "**" => Glob::AnyDepth,
+            "*" => Glob::Any,
+            "[*]" => Glob::AnyIndex,
+            _ => {
+                if s.starts_with('[') && s.ends_with(']') {
+                    if let Ok(i) = s[1..s.len() - 1].parse() {
+                        return Glob::Index(i);
+                    }
+                }
+                Glob::Name(s.to_owned())
+            }
+        }
+    }
+
+    fn matches(&self, segment: &PathSegment) -> bool {
+        match (self, segment) {
+            (&Glob::AnyDepth, _) | (&Glob::Any, _) => true,
+            (&Glob::AnyIndex, &PathSegment::Index(_)) | (&Glob::AnyIndex, &PathSegment::Key { .. }) => true,
+            (&Glob::AnyIndex, &PathSegment::Removed(_)) | (&Glob::AnyIndex, &PathSegment::Added(_)) => true,
+            (&Glob::Index(i), &PathSegment::Index(j)) => i == j,
+            (&Glob::Name(ref name), &PathSegment::Field(field)) => name == field,
+            (&Glob::Name(ref name), &PathSegment::Variant(variant)) => name == variant,
+            (&Glob::Name(ref name), segment @ &PathSegment::Key { .. })
+            | (&Glob::Name(ref name), segment @ &PathSegment::Removed(_))
+            | (&Glob::Name(ref name), segment @ &PathSegment::Added(_)) => *name == segment.to_string(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pattern(Vec<Glob>);
+
+impl Pattern {
+    fn parse(s: &str) -> Self {
+        Pattern(s.split('.').filter(|s| !s.is_empty()).map(Glob::parse).collect())
+    }
+
+    /// `path` as a whole matches the pattern
+    fn matches(&self, path: &[PathSegment]) -> bool {
+        Self::walk(&self.0, path, false)
+    }
+
+    /// `path` can be extended into something matching the pattern
+    fn matches_prefix(&self, path: &[PathSegment]) -> bool {
+        Self::walk(&self.0, path, true)
+    }
+
+    fn walk(globs: &[Glob], path: &[PathSegment], prefix: bool) -> bool {
+        match (globs.first(), path.first()) {
+            (None, None) => true,
+            (None, Some(_)) => false,
+            (Some(_), None) => prefix || globs.iter().all(|glob| *glob == Glob::AnyDepth),
+            (Some(&Glob::AnyDepth), Some(_)) => {
+                Self::walk(&globs[1..], path, prefix) || Self::walk(globs, &path[1..], prefix)
+            },
+            (Some(glob), Some(segment)) => {
+                glob.matches(segment) && Self::walk(&globs[1..], &path[1..], prefix)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pattern() {
+        let path = [PathSegment::Field("items"), PathSegment::Index(3), PathSegment::Field("cache")];
+        assert!(Pattern::parse("items.[*].cache").matches(&path));
+        assert!(Pattern::parse("items.[3].cache").matches(&path));
+        assert!(Pattern::parse("**.cache").matches(&path));
+        assert!(Pattern::parse("**").matches(&path));
+        assert!(Pattern::parse("items.**").matches(&path));
+        assert!(Pattern::parse("*.*.cache").matches(&path));
+        assert!(!Pattern::parse("items.[2].cache").matches(&path));
+        assert!(!Pattern::parse("items.*").matches(&path));
+        assert!(!Pattern::parse("*.cache").matches(&path));
+        assert!(Pattern::parse("items.*").matches_prefix(&path[..2]));
+        assert!(Pattern::parse("**.cache").matches_prefix(&path[..1]));
+        assert!(!Pattern::parse("other.**").matches_prefix(&path[..1]));
+    }
+
+    #[test]
+    fn test_options() {
+        let a = [Some(1), Some(2), Some(3)];
+        let b = [Some(2), Some(3), Some(4)];
+        let diffs = DiffOptions::new().exclude("[1]").diff(&a[..], &b[..]).unwrap();
+        assert_eq!(diffs.iter().map(|d| d.field.as_str()).collect::<Vec<_>>(), vec!["[0]", "[2]"]);
+        let diffs = DiffOptions::new().include("[1]").diff(&a[..], &b[..]).unwrap();
+        assert_eq!(diffs.iter().map(|d| d.field.as_str()).collect::<Vec<_>>(), vec!["[1]"]);
+        assert!(DiffOptions::new().exclude("[*]").diff(&a[..], &b[..]).is_none());
+        assert_eq!(DiffOptions::new().diff(&a[..], &b[..]).unwrap().len(), 3);
+        assert!(DiffOptions::new().exclude("**").diff(&1, &2).is_none());
+        assert!(DiffOptions::new().exclude("**").diff(&a[..], &b[..]).is_none());
+        assert_eq!(DiffOptions::new().exclude("[*]").diff(&1, &2).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_max_differences() {
+        let a = [1, 2, 3, 4];
+        let b = [5, 6, 7, 8];
+        let diffs = DiffOptions::new().max_differences(2).diff(&a[..], &b[..]).unwrap();
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diffs[1].field, "[1]");
+        assert_eq!(diffs[1].kind, ChangeKind::Modified);
+        assert_eq!(diffs[2].field, "[2]");
+        assert_eq!(diffs[2].kind, ChangeKind::Truncated);
+        assert_eq!(DiffOptions::new().max_differences(4).diff(&a[..], &b[..]).unwrap().len(), 4);
+        assert!(DiffOptions::new().max_differences(0).diff(&a[..], &a[..]).is_none());
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Doc {
+        name: &'static str,
+        ids: Vec<u32>,
+    }
+
+    impl Diff for Doc {
+        type Value = Doc;
+
+        fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Doc, visitor: &mut V) {
+            if visitor.enter_field(PathSegment::Field("name")) {
+                self.name.diff_with(&other.name, visitor);
+                visitor.leave_field();
+            }
+            if visitor.enter_field(PathSegment::Field("ids")) {
+                ::diff_keyed(&self.ids, &other.ids, "id", |id| id, visitor);
+                visitor.leave_field();
+            }
+        }
+    }
+
+    #[test]
+    fn test_sorted() {
+        let a = Doc { name: "a", ids: vec![10, 2] };
+        let b = Doc { name: "b", ids: vec![9, 2] };
+        let fields = |diffs: Option<Vec<Difference>>| -> Vec<String> {
+            diffs.unwrap().into_iter().map(|d| d.field).collect()
+        };
+        assert_eq!(fields(DiffOptions::new().diff(&a, &b)), vec!["name", "ids.[id=10]", "ids.[id=9]"]);
+        assert_eq!(fields(DiffOptions::new().sorted().diff(&a, &b)), vec!["ids.[id=9]", "ids.[id=10]", "name"]);
+    }
+}
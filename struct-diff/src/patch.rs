@@ -0,0 +1,155 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use chrono::{self, DateTime};
+
+use Diff;
+
+/// Changeset that brings a value to another state, generated for structs
+/// with `#[diff(patch)]` as a `FooPatch` with an optional patch per field.
+///
+/// Values without fields of their own are replaced as a whole,
+/// their patch being the new value.
+///
+/// ```
+/// use struct_diff::Patch;
+///
+/// let mut ports = vec![80, 443];
+/// let patch = ports.patch(&vec![81]).unwrap();
+/// ports.apply(&patch);
+/// assert_eq!(ports, vec![81]);
+/// ```
+pub trait Patch {
+    type Patch: Debug + Clone + PartialEq;
+
+    /// Returns `None` if `diff` would report no difference
+    fn patch(&self, other: &Self) -> Option<Self::Patch>;
+
+    /// Brings `self` to the state `patch` was made for
+    fn apply(&mut self, patch: &Self::Patch);
+
+    /// `patch` as shown by the `Debug` of a `FooPatch`: values replaced
+    /// as a whole hide their `#[diff(redact)]` fields, see `Diff::shown`
+    fn shown_patch(patch: &Self::Patch) -> &dyn Debug {
+        patch
+    }
+}
+
+macro_rules! impl_patch_as_replace {
+    ($($t: ty),*) => {
+        $(
+            impl Patch for $t {
+                type Patch = $t;
+
+                fn patch(&self, other: &Self) -> Option<Self::Patch> {
+                    if self.has_diff(other) {
+                        return Some(other.clone());
+                    }
+                    None
+                }
+
+                fn apply(&mut self, patch: &Self::Patch) {
+                    *self = patch.clone();
+                }
+
+                fn shown_patch(patch: &Self::Patch) -> &dyn Debug {
+                    patch.shown()
+                }
+            }
+        )*
+    };
+}
+
+impl_patch_as_replace!(bool, isize, i8, i16, i32, i64, usize, u8, u16, u32, u64, f32, f64, char, String,
+                       chrono::NaiveDateTime, PathBuf);
+
+macro_rules! impl_patch_for_generic {
+    ($($t: ident),*) => {
+        $(
+            impl<T> Patch for $t<T> where T: Clone + Debug + PartialEq + Diff<Value=T> {
+                type Patch = $t<T>;
+
+                fn patch(&self, other: &Self) -> Option<Self::Patch> {
+                    if self.has_diff(other) {
+                        return Some(other.clone());
+                    }
+                    None
+                }
+
+                fn apply(&mut self, patch: &Self::Patch) {
+                    *self = patch.clone();
+                }
+
+                fn shown_patch(patch: &Self::Patch) -> &dyn Debug {
+                    patch.shown()
+                }
+            }
+        )*
+    };
+}
+
+impl_patch_for_generic!(Vec, Option);
+
+macro_rules! impl_patch_for_wrapper {
+    ($($t: ident),*) => {
+        $(
+            impl<T> Patch for $t<T> where T: Clone + Debug + PartialEq + Diff<Value=T> {
+                type Patch = $t<T>;
+
+                fn patch(&self, other: &Self) -> Option<Self::Patch> {
+                    if (**self).has_diff(&**other) {
+                        return Some(other.clone());
+                    }
+                    None
+                }
+
+                fn apply(&mut self, patch: &Self::Patch) {
+                    *self = patch.clone();
+                }
+
+                fn shown_patch(patch: &Self::Patch) -> &dyn Debug {
+                    patch.shown()
+                }
+            }
+        )*
+    };
+}
+
+impl_patch_for_wrapper!(Box, Rc, Arc);
+
+impl<T: chrono::TimeZone> Patch for DateTime<T> {
+    type Patch = DateTime<T>;
+
+    fn patch(&self, other: &Self) -> Option<Self::Patch> {
+        if self.has_diff(other) {
+            return Some(other.clone());
+        }
+        None
+    }
+
+    fn apply(&mut self, patch: &Self::Patch) {
+        *self = patch.clone();
+    }
+
+    fn shown_patch(patch: &Self::Patch) -> &dyn Debug {
+        patch.shown()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_patch() {
+        let mut value = Some("a".to_owned());
+        let patch = value.patch(&None).unwrap();
+        value.apply(&patch);
+        assert_eq!(value, None);
+        assert_eq!(1.patch(&1), None);
+        assert_eq!(vec![1, 2].patch(&vec![1]), Some(vec![1]));
+        assert!(Box::new(1).patch(&Box::new(1)).is_none());
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt::{self, Debug};
+
+use serde::ser::{self, Serialize};
+
+use {diff_entry, ChangeKind, Diff, DiffVisitor, OwnedDifference, PathSegment};
+
+/// Diffs two values of any `Serialize` type, for types that can't derive `Diff`.
+///
+/// Both values are serialized into a tree first. Paths follow the derive:
+/// struct fields by name, tuple fields as `0`, `1`, .., enum fields under
+/// their variant, sequence elements as `[i]` and maps by key.
+/// Elements and keys present on one side only are reported as added or removed.
+///
+/// ```
+/// # #[macro_use] extern crate serde_derive;
+/// # extern crate struct_diff;
+/// #[derive(Serialize)]
+/// struct User { name: String, tags: Vec<&'static str> }
+///
+/// # fn main() {
+/// let a = User { name: "a".into(), tags: vec!["x"] };
+/// let b = User { name: "b".into(), tags: vec!["x", "y"] };
+/// let diffs = struct_diff::serde_diff(&a, &b).unwrap().unwrap();
+/// assert_eq!(diffs[0].to_string(), r#"name: "a" -> "b""#);
+/// assert_eq!(diffs[1].to_string(), r#"tags.[1]: <missing> -> "y""#);
+/// # }
+/// ```
+pub fn serde_diff<T>(left: &T, right: &T) -> Result<Option<Vec<OwnedDifference>>, SerializeError>
+    where T: Serialize + ?Sized
+{
+    let left = left.serialize(TreeSerializer)?;
+    let right = right.serialize(TreeSerializer)?;
+    Ok(left.diff(&right).map(|diffs| diffs.into_iter().map(|diff| diff.into_owned()).collect()))
+}
+
+/// Error raised by a `Serialize` implementation during `serde_diff`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializeError(String);
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl error::Error for SerializeError {}
+
+impl ser::Error for SerializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerializeError(msg.to_string())
+    }
+}
+
+/// Serialized value
+#[derive(Clone, PartialEq)]
+enum Node {
+    Unit,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Char(char),
+    Str(String),
+    Bytes(Vec<u8>),
+    None,
+    Some(Box<Node>),
+    Seq(Vec<Node>),
+    Map(BTreeMap<String, Node>),
+    Struct(&'static str, Fields),
+    Variant(&'static str, &'static str, Fields),
+}
+
+/// Fields of a struct or enum variant, tuple fields are named `0`, `1`, ..
+#[derive(Clone, PartialEq)]
+struct Fields {
+    tuple: bool,
+    entries: Vec<(String, Node)>,
+}
+
+impl Fields {
+    fn unit() -> Self {
+        Fields { tuple: false, entries: Vec::new() }
+    }
+
+    fn tuple(len: usize) -> Self {
+        Fields { tuple: true, entries: Vec::with_capacity(len) }
+    }
+
+    fn named(len: usize) -> Self {
+        Fields { tuple: false, entries: Vec::with_capacity(len) }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, name: Option<&'static str>, value: &T) -> Result<(), SerializeError> {
+        let name = match name {
+            Some(name) => name.to_owned(),
+            None => self.entries.len().to_string(),
+        };
+        self.entries.push((name, value.serialize(TreeSerializer)?));
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Option<&Node> {
+        self.entries.iter().find(|entry| entry.0 == name).map(|entry| &entry.1)
+    }
+
+    fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Fields, visitor: &mut V) {
+        for &(ref name, ref value) in self.entries.iter() {
+            diff_entry(PathSegment::Field(name), Some(value), other.get(name), visitor);
+        }
+        for &(ref name, ref value) in other.entries.iter().filter(|entry| self.get(&entry.0).is_none()) {
+            diff_entry(PathSegment::Field(name), None, Some(value), visitor);
+        }
+    }
+
+    fn fmt(&self, name: &str, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.entries.is_empty() {
+            f.write_str(name)
+        } else if self.tuple {
+            let mut tuple = f.debug_tuple(name);
+            for entry in self.entries.iter() {
+                tuple.field(&entry.1);
+            }
+            tuple.finish()
+        } else {
+            let mut record = f.debug_struct(name);
+            for entry in self.entries.iter() {
+                record.field(&entry.0, &entry.1);
+            }
+            record.finish()
+        }
+    }
+}
+
+/// Renders like the `Debug` of the serialized type would
+impl Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Node::Unit => f.write_str("()"),
+            Node::Bool(v) => v.fmt(f),
+            Node::I64(v) => v.fmt(f),
+            Node::U64(v) => v.fmt(f),
+            Node::F64(v) => v.fmt(f),
+            Node::Char(v) => v.fmt(f),
+            Node::Str(ref v) => v.fmt(f),
+            Node::Bytes(ref v) => v.fmt(f),
+            Node::None => f.write_str("None"),
+            Node::Some(ref v) => f.debug_tuple("Some").field(v).finish(),
+            Node::Seq(ref v) => f.debug_list().entries(v.iter()).finish(),
+            Node::Map(ref v) => f.debug_map().entries(v.iter()).finish(),
+            Node::Struct(name, ref fields) => fields.fmt(name, f),
+            Node::Variant(_, variant, ref fields) => fields.fmt(variant, f),
+        }
+    }
+}
+
+impl Diff for Node {
+    type Value = Node;
+
+    fn diff_with<'a, V: DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Self::Value, visitor: &mut V) {
+        match (self, other) {
+            (&Node::Some(ref left), &Node::Some(ref right)) => left.diff_with(right, visitor),
+            (&Node::None, &Node::Some(_)) => visitor.leaf(self, other, ChangeKind::Added),
+            (&Node::Some(_), &Node::None) => visitor.leaf(self, other, ChangeKind::Removed),
+            (&Node::Seq(ref left), &Node::Seq(ref right)) => {
+                for i in 0..left.len().max(right.len()) {
+                    diff_entry(PathSegment::Index(i), left.get(i), right.get(i), visitor);
+                }
+            },
+            (&Node::Map(ref left), &Node::Map(ref right)) => {
+                for (key, value) in left.iter() {
+                    diff_entry(PathSegment::Field(key), Some(value), right.get(key), visitor);
+                }
+                for (key, value) in right.iter().filter(|&(key, _)| !left.contains_key(key)) {
+                    diff_entry(PathSegment::Field(key), None, Some(value), visitor);
+                }
+            },
+            (&Node::Struct(left_name, ref left), &Node::Struct(right_name, ref right)) if left_name == right_name => {
+                left.diff_with(right, visitor)
+            },
+            (&Node::Variant(left_enum, from, ref left), &Node::Variant(right_enum, to, ref right)) if left_enum == right_enum => {
+                if from != to {
+                    visitor.leaf(self, other, ChangeKind::VariantChanged { from, to });
+                } else if visitor.enter_field(PathSegment::Variant(from)) {
+                    left.diff_with(right, visitor);
+                    visitor.leave_field();
+                }
+            },
+            _ => {
+                if self.ne(other) {
+                    visitor.leaf(self, other, ChangeKind::Modified);
+                }
+            },
+        }
+    }
+
+    fn has_diff(&self, other: &Self::Value) -> bool {
+        self.ne(other)
+    }
+}
+
+/// Serializes into a `Node`
+struct TreeSerializer;
+
+impl ser::Serializer for TreeSerializer {
+    type Ok = Node;
+    type Error = SerializeError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = FieldsBuilder;
+    type SerializeTupleVariant = FieldsBuilder;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = FieldsBuilder;
+    type SerializeStructVariant = FieldsBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, SerializeError> {
+        Ok(Node::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, SerializeError> {
+        Ok(Node::I64(v as i64))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, SerializeError> {
+        Ok(Node::I64(v as i64))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, SerializeError> {
+        Ok(Node::I64(v as i64))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, SerializeError> {
+        Ok(Node::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, SerializeError> {
+        Ok(Node::U64(v as u64))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, SerializeError> {
+        Ok(Node::U64(v as u64))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, SerializeError> {
+        Ok(Node::U64(v as u64))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, SerializeError> {
+        Ok(Node::U64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, SerializeError> {
+        Ok(Node::F64(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, SerializeError> {
+        Ok(Node::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, SerializeError> {
+        Ok(Node::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Node, SerializeError> {
+        Ok(Node::Str(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, SerializeError> {
+        Ok(Node::Bytes(v.to_owned()))
+    }
+
+    fn serialize_none(self) -> Result<Node, SerializeError> {
+        Ok(Node::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, SerializeError> {
+        Ok(Node::Some(Box::new(value.serialize(TreeSerializer)?)))
+    }
+
+    fn serialize_unit(self) -> Result<Node, SerializeError> {
+        Ok(Node::Unit)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Node, SerializeError> {
+        Ok(Node::Struct(name, Fields::unit()))
+    }
+
+    fn serialize_unit_variant(self, name: &'static str, _index: u32, variant: &'static str) -> Result<Node, SerializeError> {
+        Ok(Node::Variant(name, variant, Fields::unit()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<Node, SerializeError> {
+        let mut fields = Fields::tuple(1);
+        fields.push(None, value)?;
+        Ok(Node::Struct(name, fields))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, SerializeError> {
+        let mut fields = Fields::tuple(1);
+        fields.push(None, value)?;
+        Ok(Node::Variant(name, variant, fields))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, SerializeError> {
+        Ok(SeqBuilder(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, SerializeError> {
+        Ok(SeqBuilder(Vec::with_capacity(len)))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<FieldsBuilder, SerializeError> {
+        Ok(FieldsBuilder { name, variant: None, fields: Fields::tuple(len) })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<FieldsBuilder, SerializeError> {
+        Ok(FieldsBuilder { name, variant: Some(variant), fields: Fields::tuple(len) })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapBuilder, SerializeError> {
+        Ok(MapBuilder { map: BTreeMap::new(), key: None })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<FieldsBuilder, SerializeError> {
+        Ok(FieldsBuilder { name, variant: None, fields: Fields::named(len) })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<FieldsBuilder, SerializeError> {
+        Ok(FieldsBuilder { name, variant: Some(variant), fields: Fields::named(len) })
+    }
+}
+
+struct SeqBuilder(Vec<Node>);
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = Node;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        self.0.push(value.serialize(TreeSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, SerializeError> {
+        Ok(Node::Seq(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = Node;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Node, SerializeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Map keys are compared by their rendering: strings as is, anything else with `Debug`
+struct MapBuilder {
+    map: BTreeMap<String, Node>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = Node;
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerializeError> {
+        self.key = Some(match key.serialize(TreeSerializer)? {
+            Node::Str(key) => key,
+            key => format!("{:?}", key),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        let key = self.key.take().ok_or_else(|| SerializeError("map value without a key".to_owned()))?;
+        self.map.insert(key, value.serialize(TreeSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, SerializeError> {
+        Ok(Node::Map(self.map))
+    }
+}
+
+struct FieldsBuilder {
+    name: &'static str,
+    variant: Option<&'static str>,
+    fields: Fields,
+}
+
+impl FieldsBuilder {
+    fn end(self) -> Result<Node, SerializeError> {
+        Ok(match self.variant {
+            Some(variant) => Node::Variant(self.name, variant, self.fields),
+            None => Node::Struct(self.name, self.fields),
+        })
+    }
+}
+
+impl ser::SerializeTupleStruct for FieldsBuilder {
+    type Ok = Node;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        self.fields.push(None, value)
+    }
+
+    fn end(self) -> Result<Node, SerializeError> {
+        FieldsBuilder::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for FieldsBuilder {
+    type Ok = Node;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        self.fields.push(None, value)
+    }
+
+    fn end(self) -> Result<Node, SerializeError> {
+        FieldsBuilder::end(self)
+    }
+}
+
+impl ser::SerializeStruct for FieldsBuilder {
+    type Ok = Node;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerializeError> {
+        self.fields.push(Some(key), value)
+    }
+
+    fn end(self) -> Result<Node, SerializeError> {
+        FieldsBuilder::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for FieldsBuilder {
+    type Ok = Node;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerializeError> {
+        self.fields.push(Some(key), value)
+    }
+
+    fn end(self) -> Result<Node, SerializeError> {
+        FieldsBuilder::end(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    enum Shape {
+        Circle { radius: u32 },
+        Square(u32),
+        Empty,
+    }
+
+    #[derive(Serialize)]
+    struct Drawing {
+        name: &'static str,
+        shapes: Vec<Shape>,
+        owner: Option<(u8, char)>,
+        meta: HashMap<u32, bool>,
+    }
+
+    fn render(left: &Drawing, right: &Drawing) -> Vec<String> {
+        serde_diff(left, right).unwrap().unwrap_or_default().iter().map(|diff| diff.to_string()).collect()
+    }
+
+    #[test]
+    fn test_serde_diff() {
+        let a = Drawing {
+            name: "a",
+            shapes: vec![Shape::Circle { radius: 1 }, Shape::Square(2), Shape::Empty],
+            owner: None,
+            meta: vec![(1, true)].into_iter().collect(),
+        };
+        let b = Drawing {
+            name: "a",
+            shapes: vec![Shape::Circle { radius: 3 }, Shape::Empty],
+            owner: Some((1, 'x')),
+            meta: vec![(1, false), (2, true)].into_iter().collect(),
+        };
+        assert_eq!(render(&a, &b), vec![
+            "shapes.[0].Circle.radius: 1 -> 3",
+            "shapes.[1]: Square(2) -> Empty",
+            "shapes.[2]: Empty -> <missing>",
+            "owner: None -> Some([1, 'x'])",
+            "meta.1: true -> false",
+            "meta.2: <missing> -> true",
+        ]);
+        assert_eq!(render(&a, &a), Vec::<String>::new());
+
+        let diffs = serde_diff(&a, &b).unwrap().unwrap();
+        assert_eq!(diffs[1].kind, ChangeKind::VariantChanged { from: "Square", to: "Empty" });
+        assert_eq!(diffs[3].kind, ChangeKind::Added);
+    }
+}
@@ -0,0 +1,71 @@
+use {FieldEnum, FieldSet};
+
+/// Renders `col = ?, ..` for the `changed` fields, `None` if there are none.
+///
+/// Placeholders follow `changed.iter()`, i.e. declaration order,
+/// so parameters are bound by walking the same set. Column names
+/// come from `FieldEnum::column` and are not quoted.
+pub fn update_set<F: FieldEnum>(changed: &FieldSet<F>) -> Option<String> {
+    if changed.is_empty() {
+        return None;
+    }
+    let columns: Vec<_> = changed.iter().map(|field| format!("{} = ?", field.column())).collect();
+    Some(columns.join(", "))
+}
+
+/// Renders `UPDATE table SET col = ?, ..` for the `changed` fields,
+/// `None` if there is nothing to update. The `WHERE` clause is left to the caller.
+///
+/// ```ignore
+/// let changed = old.changed_fields(&new);
+/// if let Some(sql) = struct_diff::update_sql("users", &changed) {
+///     // bind `new`'s values for `changed.iter()`, then the key
+///     conn.execute(&format!("{} WHERE id = ?", sql), ..)?;
+/// }
+/// ```
+pub fn update_sql<F: FieldEnum>(table: &str, changed: &FieldSet<F>) -> Option<String> {
+    update_set(changed).map(|set| format!("UPDATE {} SET {}", table, set))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Field {
+        Name,
+        Email,
+        Age,
+    }
+
+    impl FieldEnum for Field {
+        const ALL: &'static [Field] = &[Field::Name, Field::Email, Field::Age];
+
+        fn index(self) -> usize {
+            self as usize
+        }
+
+        fn name(self) -> &'static str {
+            match self {
+                Field::Name => "name",
+                Field::Email => "email",
+                Field::Age => "age",
+            }
+        }
+
+        fn column(self) -> &'static str {
+            match self {
+                Field::Email => "email_address",
+                field => field.name(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_sql() {
+        let changed: FieldSet<Field> = vec![Field::Age, Field::Email].into_iter().collect();
+        assert_eq!(update_set(&changed).unwrap(), "email_address = ?, age = ?");
+        assert_eq!(update_sql("users", &changed).unwrap(), "UPDATE users SET email_address = ?, age = ?");
+        assert_eq!(update_sql("users", &FieldSet::<Field>::new()), None);
+    }
+}
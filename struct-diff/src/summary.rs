@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use {split_path, Difference};
+
+/// Aggregate numbers over a diff result, e.g. for dashboards.
+///
+/// Paths are split on `.` outside of brackets: a difference at `items.[2].price` counts
+/// towards the `items` top-level field and has a depth of 3.
+/// Differences of the compared value as a whole count towards `""`.
+///
+/// ```
+/// # use struct_diff::{Diff, DiffSummary};
+/// let diffs = [1, 2, 3].diff(&[1, 5, 6]).unwrap_or_default();
+/// let summary = DiffSummary::new(&diffs);
+/// assert_eq!(summary.total, 2);
+/// assert_eq!(summary.by_kind["modified"], 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffSummary {
+    /// Number of differences, truncation marker included
+    pub total: usize,
+    /// Number of differences per `ChangeKind::name`
+    pub by_kind: BTreeMap<String, usize>,
+    /// Number of differences per first path segment
+    pub by_field: BTreeMap<String, usize>,
+    /// Path with the most segments, the first one seen on ties
+    pub deepest_path: Option<String>,
+    /// Number of segments in `deepest_path`
+    pub max_depth: usize,
+}
+
+impl DiffSummary {
+    pub fn new(diffs: &[Difference]) -> Self {
+        let mut summary = DiffSummary::default();
+        for diff in diffs {
+            summary.add(diff);
+        }
+        summary
+    }
+
+    fn add(&mut self, diff: &Difference) {
+        self.total += 1;
+        *self.by_kind.entry(diff.kind.name().to_owned()).or_insert(0) += 1;
+        let segments = split_path(&diff.field);
+        let top = segments.first().cloned().unwrap_or("");
+        *self.by_field.entry(top.to_owned()).or_insert(0) += 1;
+        let depth = segments.len();
+        if self.deepest_path.is_none() || depth > self.max_depth {
+            self.deepest_path = Some(diff.field.clone());
+            self.max_depth = depth;
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b [Difference<'a>]> for DiffSummary {
+    fn from(diffs: &'b [Difference<'a>]) -> Self {
+        DiffSummary::new(diffs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use {ChangeKind, Diff};
+
+    fn difference(field: &str, kind: ChangeKind) -> Difference<'static> {
+        Difference { field: field.to_owned(), kind, left: &1, right: &2 }
+    }
+
+    #[test]
+    fn test_summary() {
+        let diffs = [
+            difference("name", ChangeKind::Modified),
+            difference("items.[0].price", ChangeKind::Modified),
+            difference("items.[2]", ChangeKind::Added),
+            difference("owner.address.city", ChangeKind::Removed),
+            difference("[key=\"a.b\"]", ChangeKind::Added),
+        ];
+        let summary = DiffSummary::new(&diffs);
+        assert_eq!(summary.total, 5);
+        assert_eq!(summary.by_kind.get("modified"), Some(&2));
+        assert_eq!(summary.by_kind.get("added"), Some(&2));
+        assert_eq!(summary.by_kind.get("removed"), Some(&1));
+        assert_eq!(summary.by_field.get("items"), Some(&2));
+        assert_eq!(summary.by_field.get("owner"), Some(&1));
+        assert_eq!(summary.by_field.get("[key=\"a.b\"]"), Some(&1));
+        assert_eq!(summary.deepest_path, Some("items.[0].price".to_owned()));
+        assert_eq!(summary.max_depth, 3);
+
+        let summary = DiffSummary::new(&1.diff(&2).unwrap());
+        assert_eq!(summary.by_field.get(""), Some(&1));
+        assert_eq!(summary.deepest_path, Some("".to_owned()));
+        assert_eq!(summary.max_depth, 0);
+
+        assert_eq!(DiffSummary::new(&[]), DiffSummary::default());
+    }
+}
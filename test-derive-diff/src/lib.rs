@@ -11,7 +11,9 @@ struct A {
 
 #[cfg(test)]
 mod tests {
-    use struct_diff::{Diff, Difference};
+    use std::fmt::Debug;
+    use struct_diff::{AuditRecord, Change, ChangeKind, Delta, Diff, DiffOptions, DiffVisitor, Difference, Entity, FieldEnum,
+                      History, Patch, PathSegment, Redacted};
 
     #[test]
     fn same() {
@@ -108,16 +110,21 @@ mod tests {
     }
 
 
-//    #[test]
-//    fn vecs_len() {
-//        #[derive(Debug, PartialEq, Diff)]
-//        struct A {
-//            v: Vec<u8>,
-//        }
-//        let a = A { v: vec![5, 7] };
-//        let b = A { v: vec![5, 7, 8] };
-//        assert_eq!(a.diff(&b).unwrap()[0].field, "v.{length}");
-//    }
+    #[test]
+    fn vecs_len() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct A {
+            v: Vec<u8>,
+        }
+        let a = A { v: vec![5, 7] };
+        let b = A { v: vec![5, 7, 8] };
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].to_string(), "v.[2]: <missing> -> 8");
+        assert_eq!(diff[0].kind, ChangeKind::Added);
+        assert!(a.has_diff(&b));
+        assert_eq!(b.diff(&a).unwrap()[0].kind, ChangeKind::Removed);
+    }
 
 
     #[test]
@@ -132,6 +139,14 @@ mod tests {
         assert_eq!(a.diff(&b).unwrap()[0].field, "A.0");
     }
 
+    #[test]
+    fn enums_empty() {
+        #[derive(Debug, PartialEq, Diff)]
+        enum Never {}
+        let has_diff: fn(&Never, &Never) -> bool = Diff::has_diff;
+        let _ = has_diff;
+    }
+
     #[test]
     fn enums_2() {
         #[derive(Debug, PartialEq, Diff)]
@@ -141,7 +156,9 @@ mod tests {
         }
         let a = A::A(5);
         let b = A::B("kek");
-        assert_eq!(a.diff(&b).unwrap()[0].field, "self");
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(diff[0].field, "");
+        assert_eq!(diff[0].kind, ChangeKind::VariantChanged { from: "A", to: "B" });
     }
 
     #[test]
@@ -153,7 +170,7 @@ mod tests {
         }
         let a = A::A(5, 6);
         let b = A::B("kek");
-        assert_eq!(a.diff(&b).unwrap()[0].field, "self");
+        assert_eq!(a.diff(&b).unwrap()[0].field, "");
     }
 
     #[test]
@@ -190,7 +207,609 @@ mod tests {
         let b = R::C { c_vec: vec![i1] };
         let c = R::S { t_vec: vec![A { a: "test".into()} ]};
         assert_eq!(a.diff(&b).unwrap()[0].field, "C.c_vec.[0].S.t_vec.[0].a");
-        assert_eq!(a.diff(&c).unwrap()[0].field, "self");
+        assert_eq!(a.diff(&c).unwrap()[0].field, "");
+        assert_eq!(a.diff(&c).unwrap()[0].kind, ChangeKind::VariantChanged { from: "C", to: "S" });
+    }
+
+    #[test]
+    fn enum_variant_path() {
+        #[derive(Debug, PartialEq, Diff)]
+        enum Status {
+            Active,
+            Suspended { reason: String },
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        struct Account {
+            status: Status,
+        }
+        let a = Account { status: Status::Active };
+        let b = Account { status: Status::Suspended { reason: "spam".into() } };
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].field, "status");
+        assert_eq!(diff[0].kind, ChangeKind::VariantChanged { from: "Active", to: "Suspended" });
+        assert_eq!(diff[0].to_string(), "status: Active -> Suspended { reason: \"spam\" }");
+    }
+
+    #[test]
+    fn has_diff() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct A {
+            a: u32,
+            b: Vec<B>,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        enum B {
+            X,
+            Y(u32),
+            Z { z: String },
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        struct C(u32, B);
+
+        let a = A { a: 1, b: vec![B::X, B::Y(1), B::Z { z: "z".into() }] };
+        assert!(!a.has_diff(&a));
+        let b = A { a: 1, b: vec![B::X, B::Y(2), B::Z { z: "z".into() }] };
+        assert!(a.has_diff(&b));
+        let b = A { a: 1, b: vec![B::X, B::Y(1), B::Z { z: "w".into() }] };
+        assert!(a.has_diff(&b));
+        let b = A { a: 1, b: vec![B::Y(1), B::Y(1), B::Z { z: "z".into() }] };
+        assert!(a.has_diff(&b));
+        assert!(!C(1, B::X).has_diff(&C(1, B::X)));
+        assert!(C(1, B::X).has_diff(&C(2, B::X)));
+    }
+
+    #[test]
+    fn visitor() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct A {
+            a: u32,
+            b: Vec<B>,
+            c: String,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        enum B {
+            X(u32),
+        }
+
+        // skips everything under `b` and counts the rest
+        struct Count(usize);
+
+        impl<'a> DiffVisitor<'a> for Count {
+            fn enter_field(&mut self, segment: PathSegment<'a>) -> bool {
+                segment != PathSegment::Field("b")
+            }
+
+            fn leaf(&mut self, _left: &'a Debug, _right: &'a Debug, _kind: ChangeKind) {
+                self.0 += 1;
+            }
+        }
+
+        let a = A { a: 1, b: vec![B::X(1)], c: "c".into() };
+        let b = A { a: 2, b: vec![B::X(2)], c: "d".into() };
+        let mut count = Count(0);
+        a.diff_with(&b, &mut count);
+        assert_eq!(count.0, 2);
+        assert_eq!(a.diff(&b).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn options_filter() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct Order {
+            updated_at: u64,
+            items: Vec<Item>,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        struct Item {
+            qty: u32,
+            cache: String,
+            updated_at: u64,
+        }
+
+        let a = Order { updated_at: 1, items: vec![Item { qty: 1, cache: "a".into(), updated_at: 1 }] };
+        let b = Order { updated_at: 2, items: vec![Item { qty: 2, cache: "b".into(), updated_at: 2 }] };
+        let fields = |diffs: Option<Vec<Difference>>| {
+            diffs.unwrap_or_default().into_iter().map(|d| d.field).collect::<Vec<_>>()
+        };
+        assert_eq!(fields(DiffOptions::new().exclude("**.updated_at").diff(&a, &b)), vec!["items.[0].qty", "items.[0].cache"]);
+        assert_eq!(fields(DiffOptions::new().exclude("items.[*].cache").diff(&a, &b)), vec!["updated_at", "items.[0].qty", "items.[0].updated_at"]);
+        assert_eq!(fields(DiffOptions::new().include("items").exclude("**.updated_at").diff(&a, &b)), vec!["items.[0].qty", "items.[0].cache"]);
+        assert_eq!(fields(DiffOptions::new().include("**.qty").include("updated_at").diff(&a, &b)), vec!["updated_at", "items.[0].qty"]);
+    }
+
+    #[test]
+    fn options_limits() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct A {
+            b: B,
+            v: Vec<B>,
+            e: E,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        struct B {
+            x: u32,
+            y: u32,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        enum E {
+            C(B),
+        }
+
+        let a = A { b: B { x: 1, y: 1 }, v: vec![B { x: 1, y: 1 }], e: E::C(B { x: 1, y: 1 }) };
+        let b = A { b: B { x: 2, y: 2 }, v: vec![B { x: 2, y: 2 }], e: E::C(B { x: 2, y: 2 }) };
+        assert_eq!(a.diff(&b).unwrap().len(), 6);
+
+        let diffs = DiffOptions::new().max_depth(1).diff(&a, &b).unwrap();
+        assert_eq!(diffs.iter().map(|d| d.field.as_str()).collect::<Vec<_>>(), vec!["b", "v", "e"]);
+        assert_eq!(format!("{:?}", diffs[0].left), "B { x: 1, y: 1 }");
+
+        let diffs = DiffOptions::new().max_depth(2).diff(&a, &b).unwrap();
+        assert_eq!(diffs.iter().map(|d| d.field.as_str()).collect::<Vec<_>>(), vec!["b.x", "b.y", "v.[0]", "e.C"]);
+        assert_eq!(format!("{:?}", diffs[3].right), "C(B { x: 2, y: 2 })");
+
+        let diffs = DiffOptions::new().max_differences(1).diff(&a, &b).unwrap();
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[1].field, "b.y");
+        assert_eq!(diffs[1].kind, ChangeKind::Truncated);
+    }
+
+    #[test]
+    fn redact() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct User {
+            name: String,
+            #[diff(redact)]
+            password: String,
+            token: Token,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        enum Token {
+            None,
+            Bearer(#[diff(redact)] String),
+        }
+
+        let a = User { name: "a".into(), password: "hunter2".into(), token: Token::Bearer("t1".into()) };
+        let b = User { name: "a".into(), password: "hunter3".into(), token: Token::Bearer("t2".into()) };
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(diff.len(), 2);
+        assert_eq!(diff[0].to_string(), "password: <redacted> -> <redacted>");
+        assert_eq!(diff[1].to_string(), "token.Bearer.0: <redacted> -> <redacted>");
+        assert!(a.diff(&a).is_none());
+
+        let c = User { name: "a".into(), password: "hunter2".into(), token: Token::None };
+        let diff = a.diff(&c).unwrap();
+        assert_eq!(diff[0].to_string(), "token: Bearer(<redacted>) -> None");
+        assert_eq!(diff[0].kind, ChangeKind::VariantChanged { from: "Bearer", to: "None" });
+
+        #[derive(Debug, PartialEq, Diff)]
+        struct Session {
+            #[diff(redact)]
+            token: Option<String>,
+        }
+        let none = Session { token: None };
+        let some = Session { token: Some("t1".into()) };
+        let diff = none.diff(&some).unwrap();
+        assert_eq!(diff[0].to_string(), "token: <redacted> -> <redacted>");
+        assert_eq!(diff[0].kind, ChangeKind::Added);
+        assert_eq!(some.diff(&none).unwrap()[0].kind, ChangeKind::Removed);
+        let other = Session { token: Some("t2".into()) };
+        assert_eq!(some.diff(&other).unwrap()[0].kind, ChangeKind::Modified);
+    }
+
+    #[test]
+    fn flatten() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct Meta {
+            created_by: String,
+            version: u32,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        struct Document {
+            title: String,
+            #[diff(flatten)]
+            meta: Meta,
+        }
+
+        let a = Document { title: "a".into(), meta: Meta { created_by: "bob".into(), version: 1 } };
+        let b = Document { title: "b".into(), meta: Meta { created_by: "eve".into(), version: 1 } };
+        let fields: Vec<_> = a.diff(&b).unwrap().into_iter().map(|d| d.field).collect();
+        assert_eq!(fields, vec!["title", "created_by"]);
+        let diff = DiffOptions::new().exclude("created_by").diff(&a, &b).unwrap();
+        assert_eq!(diff.len(), 1);
+    }
+
+    #[test]
+    fn redact_whole_values() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct Creds {
+            id: u32,
+            #[diff(redact)]
+            password: String,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        struct Account {
+            creds: Option<Creds>,
+            inner: Creds,
+            #[diff(unordered)]
+            pool: Vec<Creds>,
+            #[diff(key = "id")]
+            keyed: Vec<Creds>,
+        }
+
+        let creds = |id, password: &str| Creds { id, password: password.into() };
+        let a = Account { creds: None, inner: creds(1, "s1"), pool: vec![creds(2, "p2")], keyed: vec![creds(3, "k3")] };
+        let b = Account { creds: Some(creds(0, "hunter2")), inner: creds(1, "s2"), pool: vec![creds(4, "p4")], keyed: vec![creds(5, "k5")] };
+
+        let rendered: Vec<_> = a.diff(&b).unwrap().iter().map(|d| d.to_string()).collect();
+        let shown = |id| format!("Creds {{ id: {}, password: <redacted> }}", id);
+        assert_eq!(rendered, vec![
+            format!("creds: None -> Some({})", shown(0)),
+            "inner.password: <redacted> -> <redacted>".to_owned(),
+            format!("pool.[-0]: {} -> <missing>", shown(2)),
+            format!("pool.[+0]: <missing> -> {}", shown(4)),
+            format!("keyed.[id=3]: {} -> <missing>", shown(3)),
+            format!("keyed.[id=5]: <missing> -> {}", shown(5)),
+        ]);
+
+        let collapsed = DiffOptions::new().max_depth(1).include("inner").diff(&a, &b).unwrap();
+        assert_eq!(collapsed[0].to_string(), format!("inner: {} -> {}", shown(1), shown(1)));
+        let collapsed = DiffOptions::new().max_depth(1).include("pool").diff(&a, &b).unwrap();
+        assert_eq!(collapsed[0].to_string(), format!("pool: [{}] -> [{}]", shown(2), shown(4)));
+
+        let rendered = format!("{:?}", a.diff(&b).unwrap());
+        for password in &["hunter2", "s1", "s2", "p2", "p4", "k3", "k5"] {
+            assert!(!rendered.contains(password), "{} leaked", password);
+        }
+    }
+
+    #[test]
+    fn keyed() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct LineItem {
+            id: u32,
+            qty: u32,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        struct Order {
+            #[diff(key = "id")]
+            items: Vec<LineItem>,
+        }
+
+        let a = Order { items: vec![LineItem { id: 1, qty: 1 }, LineItem { id: 42, qty: 2 }] };
+        let b = Order { items: vec![LineItem { id: 42, qty: 3 }, LineItem { id: 7, qty: 1 }] };
+        let diff = a.diff(&b).unwrap();
+        let rendered: Vec<_> = diff.iter().map(|d| (d.field.as_str(), d.kind)).collect();
+        assert_eq!(rendered, vec![
+            ("items.[id=1]", ChangeKind::Removed),
+            ("items.[id=42].qty", ChangeKind::Modified),
+            ("items.[id=7]", ChangeKind::Added),
+        ]);
+        assert!(a.has_diff(&b));
+
+        let reordered = Order { items: vec![LineItem { id: 42, qty: 2 }, LineItem { id: 1, qty: 1 }] };
+        assert!(a.diff(&reordered).is_none());
+        assert!(!a.has_diff(&reordered));
+        let diff = DiffOptions::new().include("items.[id=42]").diff(&a, &b).unwrap();
+        assert_eq!(diff.len(), 1);
+
+        let duplicated = Order { items: vec![LineItem { id: 1, qty: 1 }, LineItem { id: 1, qty: 1 }] };
+        let grown = Order { items: vec![LineItem { id: 1, qty: 1 }, LineItem { id: 2, qty: 1 }] };
+        assert!(duplicated.has_diff(&grown));
+        let rendered: Vec<_> = duplicated.diff(&grown).unwrap().iter().map(|d| (d.field.clone(), d.kind)).collect();
+        assert_eq!(rendered, vec![("items.[id=1#2]".to_owned(), ChangeKind::Removed), ("items.[id=2]".to_owned(), ChangeKind::Added)]);
+
+        let bumped = Order { items: vec![LineItem { id: 1, qty: 5 }, LineItem { id: 1, qty: 6 }] };
+        let rendered: Vec<_> = duplicated.diff(&bumped).unwrap().iter().map(|d| d.to_string()).collect();
+        assert_eq!(rendered, vec!["items.[id=1].qty: 1 -> 5", "items.[id=1#2].qty: 1 -> 6"]);
+        let diff = DiffOptions::new().exclude("items.[id=1#2]").diff(&duplicated, &bumped).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].field, "items.[id=1].qty");
+    }
+
+    #[test]
+    fn unordered() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct User {
+            #[diff(unordered)]
+            roles: Vec<String>,
+        }
+
+        let a = User { roles: vec!["admin".into(), "dev".into(), "ops".into()] };
+        let b = User { roles: vec!["ops".into(), "admin".into(), "qa".into()] };
+        let diff = a.diff(&b).unwrap();
+        let rendered: Vec<_> = diff.iter().map(|d| d.to_string()).collect();
+        assert_eq!(rendered, vec!["roles.[-1]: \"dev\" -> <missing>", "roles.[+2]: <missing> -> \"qa\""]);
+        assert_eq!(diff[0].kind, ChangeKind::Removed);
+        assert_eq!(diff[1].kind, ChangeKind::Added);
+
+        let shuffled = User { roles: vec!["ops".into(), "admin".into(), "dev".into()] };
+        assert!(a.diff(&shuffled).is_none());
+        assert!(!a.has_diff(&shuffled));
+    }
+
+    #[test]
+    fn delta() {
+        #[derive(Debug, PartialEq, Diff)]
+        #[diff(delta)]
+        struct Price {
+            amount: u64,
+            currency: String,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        #[diff(delta)]
+        struct Product {
+            name: String,
+            price: Price,
+            tags: Vec<String>,
+            #[diff(redact)]
+            secret: String,
+        }
+
+        let a = Product { name: "a".into(), price: Price { amount: 10, currency: "EUR".into() }, tags: vec![], secret: "x".into() };
+        let b = Product { name: "a".into(), price: Price { amount: 12, currency: "EUR".into() }, tags: vec![], secret: "y".into() };
+        let delta: ProductDelta = a.delta(&b).unwrap();
+        assert_eq!(delta.name, None);
+        assert_eq!(delta.tags, None);
+        let price = delta.price.unwrap();
+        assert_eq!(price.amount, Some(Change { from: &10, to: &12 }));
+        assert_eq!(price.currency, None);
+        assert_eq!(delta.secret, Some(Change { from: &Redacted, to: &Redacted }));
+        assert!(a.delta(&a).is_none());
+    }
+
+    #[test]
+    fn delta_redact_whole_values() {
+        #[derive(Debug, PartialEq, Diff)]
+        #[diff(delta)]
+        struct Creds {
+            id: u32,
+            #[diff(redact)]
+            password: String,
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        #[diff(delta)]
+        struct Account {
+            creds: Option<Creds>,
+            inner: Creds,
+            #[diff(unordered)]
+            pool: Vec<Creds>,
+            #[diff(key = "id")]
+            keyed: Vec<Creds>,
+        }
+
+        let creds = |id, password: &str| Creds { id, password: password.into() };
+        let a = Account { creds: None, inner: creds(1, "s1"), pool: vec![creds(2, "p2")], keyed: vec![creds(3, "k3")] };
+        let b = Account { creds: Some(creds(0, "hunter2")), inner: creds(1, "s2"), pool: vec![creds(4, "p4")], keyed: vec![creds(5, "k5")] };
+
+        let delta = a.delta(&b).unwrap();
+        assert_eq!(delta.inner.as_ref().unwrap().password, Some(Change { from: &Redacted, to: &Redacted }));
+        let rendered = format!("{:?}", delta);
+        assert!(rendered.contains("Creds { id: 4, password: <redacted> }"), "{}", rendered);
+        for password in &["hunter2", "s1", "s2", "p2", "p4", "k3", "k5"] {
+            assert!(!rendered.contains(password), "{} leaked", password);
+        }
+    }
+
+    #[test]
+    fn delta_enum_field() {
+        #[derive(Debug, PartialEq, Diff)]
+        enum Status {
+            Draft,
+            Published { at: u64 },
+        }
+        #[derive(Debug, PartialEq, Diff)]
+        #[diff(delta)]
+        struct Post {
+            title: String,
+            status: Status,
+        }
+
+        let a = Post { title: "a".into(), status: Status::Draft };
+        let b = Post { title: "a".into(), status: Status::Published { at: 1 } };
+        let c = Post { title: "a".into(), status: Status::Published { at: 2 } };
+        assert_eq!(a.delta(&b).unwrap().status, Some(Change { from: &a.status, to: &b.status }));
+        assert_eq!(b.delta(&c).unwrap().status, Some(Change { from: &b.status, to: &c.status }));
+        assert_eq!(a.delta(&b).unwrap().title, None);
+        assert!(b.status.delta(&b.status).is_none());
+    }
+
+    #[test]
+    fn patch_redact_whole_values() {
+        #[derive(Debug, Clone, PartialEq, Diff)]
+        struct Creds {
+            id: u32,
+            #[diff(redact)]
+            password: String,
+        }
+        #[derive(Debug, Clone, PartialEq, Diff)]
+        #[diff(patch)]
+        enum Auth {
+            Anonymous,
+            Basic(#[diff(redact)] String),
+        }
+        #[derive(Debug, Clone, PartialEq, Diff)]
+        #[diff(patch)]
+        struct Account {
+            creds: Option<Creds>,
+            pool: Vec<Creds>,
+            auth: Auth,
+        }
+
+        let creds = |id, password: &str| Creds { id, password: password.into() };
+        let a = Account { creds: None, pool: vec![creds(1, "p1")], auth: Auth::Anonymous };
+        let b = Account { creds: Some(creds(0, "hunter2")), pool: vec![creds(2, "p2")], auth: Auth::Basic("b1".into()) };
+
+        let patch = a.patch(&b).unwrap();
+        let rendered = format!("{:?}", patch);
+        assert!(rendered.contains("pool: Some([Creds { id: 2, password: <redacted> }])"), "{}", rendered);
+        assert!(rendered.contains("auth: Some(Basic(<redacted>))"), "{}", rendered);
+        for password in &["hunter2", "p1", "p2", "b1"] {
+            assert!(!rendered.contains(password), "{} leaked", password);
+        }
+        let mut patched = a.clone();
+        patched.apply(&patch);
+        assert_eq!(patched, b);
+    }
+
+    #[test]
+    fn patch() {
+        #[derive(Debug, Clone, PartialEq, Diff)]
+        #[diff(patch)]
+        enum Status {
+            Draft,
+            Published { at: u64 },
+        }
+        #[derive(Debug, Clone, PartialEq, Diff)]
+        #[diff(patch)]
+        struct Price {
+            amount: u64,
+            currency: String,
+        }
+        #[derive(Debug, Clone, PartialEq, Diff)]
+        #[diff(patch)]
+        struct Product {
+            name: String,
+            price: Price,
+            status: Status,
+            tags: Vec<String>,
+            #[diff(redact)]
+            secret: String,
+        }
+
+        let v0 = Product {
+            name: "a".into(),
+            price: Price { amount: 10, currency: "EUR".into() },
+            status: Status::Draft,
+            tags: vec![],
+            secret: "x".into(),
+        };
+        let mut v1 = v0.clone();
+        v1.price.amount = 12;
+        v1.status = Status::Published { at: 1 };
+        let mut v2 = v1.clone();
+        v2.tags.push("new".into());
+        v2.secret = "y".into();
+
+        let patch = v0.patch(&v1).unwrap();
+        assert_eq!(patch.name, None);
+        assert_eq!(patch.price, Some(PricePatch { amount: Some(12), currency: None }));
+        assert_eq!(patch.status, Some(Status::Published { at: 1 }));
+        assert!(v0.patch(&v0).is_none());
+        let mut patched = v0.clone();
+        patched.apply(&patch);
+        assert_eq!(patched, v1);
+
+        let patch = v1.patch(&v2).unwrap();
+        let shown = format!("{:?}", patch);
+        assert!(shown.contains("secret: Some(<redacted>)"), "{}", shown);
+        assert!(!shown.contains("\"y\""), "{}", shown);
+
+        let mut history = History::new(v0.clone());
+        assert_eq!(history.record(v1.clone()), Some(1));
+        assert_eq!(history.record(v2.clone()), Some(2));
+        assert_eq!(history.at(0), v0);
+        assert_eq!(history.at(1), v1);
+        assert_eq!(history.at(2), v2);
+    }
+
+    #[test]
+    fn changed_fields() {
+        #[derive(Debug, PartialEq, Diff)]
+        #[diff(fields)]
+        struct Row {
+            id: u32,
+            display_name: String,
+            #[diff(unordered)]
+            tags: Vec<String>,
+        }
+
+        let a = Row { id: 1, display_name: "a".into(), tags: vec!["x".into(), "y".into()] };
+        let b = Row { id: 1, display_name: "b".into(), tags: vec!["y".into(), "x".into()] };
+        let changed = a.changed_fields(&b);
+        assert_eq!(changed.iter().collect::<Vec<_>>(), vec![RowField::DisplayName]);
+        assert!(changed.contains(RowField::DisplayName));
+        assert!(!changed.contains(RowField::Tags));
+        assert_eq!(format!("{:?}", changed), r#"{"display_name"}"#);
+        assert!(a.changed_fields(&a).is_empty());
+        assert_eq!(RowField::ALL.len(), 3);
+    }
+
+    #[test]
+    fn changed_fields_grown_vec() {
+        #[derive(Debug, PartialEq, Diff)]
+        #[diff(fields, delta)]
+        struct Row {
+            id: u32,
+            tags: Vec<String>,
+        }
+
+        let a = Row { id: 1, tags: vec!["x".into()] };
+        let b = Row { id: 1, tags: vec!["x".into(), "y".into()] };
+        assert_eq!(a.changed_fields(&b).iter().collect::<Vec<_>>(), vec![RowField::Tags]);
+        assert_eq!(b.changed_fields(&a).iter().collect::<Vec<_>>(), vec![RowField::Tags]);
+        assert_eq!(struct_diff::update_sql("rows", &a.changed_fields(&b)).unwrap(), "UPDATE rows SET tags = ?");
+        assert_eq!(a.delta(&b).unwrap().tags, Some(Change { from: &a.tags, to: &b.tags }));
+    }
+
+    #[test]
+    fn update_sql() {
+        #[derive(Debug, PartialEq, Diff)]
+        #[diff(fields)]
+        struct User {
+            id: u32,
+            name: String,
+            #[diff(column = "email_address")]
+            email: String,
+        }
+
+        let a = User { id: 1, name: "a".into(), email: "a@a".into() };
+        let b = User { id: 1, name: "b".into(), email: "b@b".into() };
+        assert_eq!(UserField::Email.column(), "email_address");
+        assert_eq!(UserField::Name.column(), "name");
+        let changed = a.changed_fields(&b);
+        assert_eq!(struct_diff::update_sql("users", &changed).unwrap(), "UPDATE users SET name = ?, email_address = ?");
+        assert_eq!(struct_diff::update_sql("users", &a.changed_fields(&a)), None);
+    }
+
+    #[test]
+    fn audit_record() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct Account {
+            #[diff(entity_id)]
+            id: u64,
+            plan: String,
+        }
+
+        let a = Account { id: 42, plan: "free".into() };
+        let b = Account { id: 42, plan: "pro".into() };
+        assert_eq!(b.entity_name(), "Account");
+        assert_eq!(b.entity_id(), "42");
+        let record = AuditRecord::new("support", &a, &b).unwrap();
+        assert_eq!(record.entity, "Account");
+        assert_eq!(record.entity_id, "42");
+        assert_eq!(record.actor, "support");
+        assert_eq!(record.changes.len(), 1);
+        assert_eq!(record.changes[0].to_string(), "plan: \"free\" -> \"pro\"");
+    }
+
+    #[test]
+    fn change_kinds() {
+        #[derive(Debug, PartialEq, Diff)]
+        struct A {
+            a: Option<u32>,
+            b: Option<u32>,
+            c: Option<u32>,
+        }
+        let a = A { a: None, b: Some(1), c: Some(1) };
+        let b = A { a: Some(1), b: None, c: Some(2) };
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(diff[0].field, "a");
+        assert_eq!(diff[0].kind, ChangeKind::Added);
+        assert_eq!(diff[1].field, "b");
+        assert_eq!(diff[1].kind, ChangeKind::Removed);
+        assert_eq!(diff[2].field, "c");
+        assert_eq!(diff[2].kind, ChangeKind::Modified);
     }
 
     #[test]
@@ -0,0 +1,163 @@
+pub extern crate struct_diff;
+pub extern crate tracing;
+
+use std::fmt;
+use struct_diff::Difference;
+use tracing::Span;
+
+/// Renders differences as `path: left -> right; ..`,
+/// to be recorded as a field with `diff = %Differences(&diffs)`
+pub struct Differences<'a: 'b, 'b>(pub &'b [Difference<'a>]);
+
+impl<'a, 'b> fmt::Display for Differences<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, diff) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", diff)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records `diff.count` and `diff` on a span that declared them,
+/// e.g. `info_span!("save", diff.count = Empty, diff = Empty)`.
+///
+/// `diff` is a single string rendered by `Differences`: span fields must be
+/// declared when the span is created, so there can't be one per path.
+pub fn record_diff(span: &Span, diffs: &[Difference]) {
+    span.record("diff.count", diffs.len());
+    span.record("diff", tracing::field::display(Differences(diffs)));
+}
+
+/// Diffs two values and records the result as a single event
+/// with `diff.count` and `diff`, rendered by `Differences`.
+///
+/// Nothing is diffed unless the level is enabled. It is `TRACE` unless another one is given:
+///
+/// ```
+/// # #[macro_use] extern crate tracing_struct_diff;
+/// # fn main() {
+/// let (old, new) = (1, 2);
+/// trace_diff!(old, new);
+/// trace_diff!(level: tracing_struct_diff::tracing::Level::INFO, old, new);
+/// # }
+/// ```
+///
+/// Differences can't be recorded as structured values: field names are fixed
+/// where the event is declared, `tracing::Value` is sealed to primitives,
+/// and nested values through `valuable` need the `tracing_unstable` cfg.
+#[macro_export]
+macro_rules! trace_diff {
+    ($left:expr, $right:expr) => {
+        $crate::trace_diff!(level: $crate::tracing::Level::TRACE, $left, $right)
+    };
+    (level: $level:expr, $left:expr, $right:expr) => {{
+        if $crate::tracing::enabled!($level) {
+            use $crate::struct_diff::Diff;
+            let diffs = ($left).diff(&$right).unwrap_or_default();
+            $crate::tracing::event!(
+                $level,
+                diff.count = diffs.len(),
+                diff = %$crate::Differences(&diffs),
+                "diff"
+            );
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use struct_diff::Diff;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Collects fields of all events and span records as `name=value`,
+    /// enabling only events and spans up to `DEBUG`
+    struct Capture(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Capture {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.lock().unwrap().push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            *metadata.level() <= tracing::Level::DEBUG
+        }
+
+        fn new_span(&self, _span: &Attributes) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, values: &Record) {
+            values.record(&mut Capture(self.0.clone()));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event) {
+            event.record(&mut Capture(self.0.clone()));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    fn capture<F: FnOnce()>(f: F) -> Vec<String> {
+        let fields = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Capture(fields.clone()), f);
+        let fields = fields.lock().unwrap();
+        fields.clone()
+    }
+
+    #[test]
+    fn trace_diff_event() {
+        let fields = capture(|| trace_diff!(level: tracing::Level::DEBUG, [1, 2, 3], [1, 5, 4]));
+        assert_eq!(fields, vec!["message=diff", "diff.count=2", "diff=[1]: 2 -> 5; [2]: 3 -> 4"]);
+    }
+
+    #[test]
+    fn trace_diff_disabled() {
+        struct Panics;
+
+        impl PartialEq for Panics {
+            fn eq(&self, _other: &Panics) -> bool {
+                panic!("diffed although TRACE is disabled")
+            }
+        }
+
+        impl fmt::Debug for Panics {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("Panics")
+            }
+        }
+
+        impl Diff for Panics {
+            type Value = Panics;
+
+            fn diff_with<'a, V: struct_diff::DiffVisitor<'a> + ?Sized>(&'a self, other: &'a Panics, visitor: &mut V) {
+                if self != other {
+                    visitor.leaf(self, other, struct_diff::ChangeKind::Modified);
+                }
+            }
+        }
+
+        assert!(capture(|| trace_diff!(Panics, Panics)).is_empty());
+    }
+
+    #[test]
+    fn record_diff_on_span() {
+        let fields = capture(|| {
+            let span = tracing::info_span!("save", diff.count = tracing::field::Empty, diff = tracing::field::Empty);
+            record_diff(&span, &[1, 2].diff(&[3, 2]).unwrap());
+        });
+        assert_eq!(fields, vec!["diff.count=1", "diff=[0]: 1 -> 3"]);
+    }
+}